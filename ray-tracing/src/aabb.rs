@@ -0,0 +1,91 @@
+use crate::ray::{Point, Ray};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Aabb {
+    minimum: Point,
+    maximum: Point,
+}
+
+impl Aabb {
+    pub fn new(minimum: Point, maximum: Point) -> Self {
+        Self { minimum, maximum }
+    }
+
+    pub fn min(&self) -> Point {
+        self.minimum
+    }
+
+    pub fn max(&self) -> Point {
+        self.maximum
+    }
+
+    pub fn centroid(&self) -> Point {
+        0.5 * (self.minimum + self.maximum)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.maximum - self.minimum;
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    /// Returns a box that is at least `delta` wide along every axis, so
+    /// that flat objects still get hit.
+    pub fn padded(&self, delta: f64) -> Self {
+        let mut minimum = self.minimum;
+        let mut maximum = self.maximum;
+
+        for a in 0..3 {
+            let size = maximum[a] - minimum[a];
+            if size < delta {
+                let grow = (delta - size) / 2.0;
+                minimum[a] -= grow;
+                maximum[a] += grow;
+            }
+        }
+
+        Self::new(minimum, maximum)
+    }
+
+    /// The axis along which the box is the widest.
+    pub fn longest_axis(&self) -> usize {
+        let d = self.maximum - self.minimum;
+        if d.x() > d.y() && d.x() > d.z() {
+            0
+        } else if d.y() > d.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn hit(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
+        let origin = r.origin();
+        let direction = r.direction();
+
+        for a in 0..3 {
+            let inv_d = 1.0 / direction[a];
+            let mut t0 = (self.minimum[a] - origin[a]) * inv_d;
+            let mut t1 = (self.maximum[a] - origin[a]) * inv_d;
+
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+
+            if t_max <= t_min {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+pub fn surrounding_box(box0: &Aabb, box1: &Aabb) -> Aabb {
+    let small = box0.min().min(&box1.min());
+    let big = box0.max().max(&box1.max());
+
+    Aabb::new(small, big)
+}
@@ -0,0 +1,138 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Mat,
+    ray::{Point, Ray, Vec3},
+};
+
+const BOX_PADDING: f64 = 1e-4;
+
+/// A rectangle perpendicular to the axis `K`.
+///
+/// The rectangle spans `a0..a1` and `b0..b1` along the two other axes, in
+/// their natural order, and lies at `k` on the axis `K`.
+pub struct AaRect<const K: usize> {
+    pub a0: f64,
+    pub a1: f64,
+    pub b0: f64,
+    pub b1: f64,
+    pub k: f64,
+    pub mat: Mat,
+}
+
+/// A rectangle in the XY plane.
+pub type XyRect = AaRect<2>;
+/// A rectangle in the XZ plane.
+pub type XzRect = AaRect<1>;
+/// A rectangle in the YZ plane.
+pub type YzRect = AaRect<0>;
+
+impl<const K: usize> AaRect<K> {
+    const A: usize = if K == 0 { 1 } else { 0 };
+    const B: usize = if K == 2 { 1 } else { 2 };
+
+    pub fn new(a0: f64, a1: f64, b0: f64, b1: f64, k: f64, mat: Mat) -> Self {
+        Self {
+            a0,
+            a1,
+            b0,
+            b1,
+            k,
+            mat,
+        }
+    }
+
+    fn point(&self, a: f64, b: f64, k: f64) -> Point {
+        let mut p = Point::default();
+        p[Self::A] = a;
+        p[Self::B] = b;
+        p[K] = k;
+        p
+    }
+}
+
+impl<const K: usize> Hittable for AaRect<K> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let origin = r.origin();
+        let direction = r.direction();
+
+        let t = (self.k - origin[K]) / direction[K];
+        if !(t_min..=t_max).contains(&t) {
+            return false;
+        }
+
+        let a = origin[Self::A] + t * direction[Self::A];
+        let b = origin[Self::B] + t * direction[Self::B];
+        if a < self.a0 || a > self.a1 || b < self.b0 || b > self.b1 {
+            return false;
+        }
+
+        rec.u = (a - self.a0) / (self.a1 - self.a0);
+        rec.v = (b - self.b0) / (self.b1 - self.b0);
+        rec.tangent = Vec3::default();
+        rec.tangent[Self::A] = self.a1 - self.a0;
+        rec.bitangent = Vec3::default();
+        rec.bitangent[Self::B] = self.b1 - self.b0;
+        rec.t = t;
+
+        let mut outward_normal = Vec3::default();
+        outward_normal[K] = 1.0;
+        rec.set_face_normal(r, &outward_normal);
+        rec.mat = Some(self.mat.clone());
+        rec.p = r.at(t);
+
+        true
+    }
+
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        let min = self.point(self.a0, self.b0, self.k);
+        let max = self.point(self.a1, self.b1, self.k);
+        *output_box = Aabb::new(min, max).padded(BOX_PADDING);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{material::Lambartian, render::Color};
+
+    fn mat() -> Mat {
+        Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn test_hit() {
+        let rect = XzRect::new(-1.0, 1.0, 0.0, 4.0, 2.0, mat());
+        let r = Ray::new(Point::new(0.5, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0));
+        let mut rec = HitRecord::default();
+
+        assert!(rect.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert_eq!(rec.t, 2.0);
+        assert_eq!(rec.p, Point::new(0.5, 2.0, 1.0));
+        assert_eq!(rec.normal, Vec3::new(0.0, -1.0, 0.0));
+        assert!(!rec.front_face);
+        assert_eq!((rec.u, rec.v), (0.75, 0.25));
+
+        let miss = Ray::new(Point::new(0.5, 0.0, 5.0), Vec3::new(0.0, 1.0, 0.0));
+        assert!(!rect.hit(&miss, 0.001, f64::INFINITY, &mut rec));
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let mut bbox = Aabb::default();
+
+        assert!(YzRect::new(1.0, 2.0, 3.0, 4.0, 5.0, mat()).bounding_box(&mut bbox));
+        assert_eq!(bbox.min().y(), 1.0);
+        assert_eq!(bbox.max().z(), 4.0);
+        assert!(bbox.min().x() < 5.0 && bbox.max().x() > 5.0);
+
+        assert!(XyRect::new(1.0, 2.0, 3.0, 4.0, 5.0, mat()).bounding_box(&mut bbox));
+        assert_eq!(bbox.min().x(), 1.0);
+        assert_eq!(bbox.max().y(), 4.0);
+        assert!(bbox.min().z() < 5.0 && bbox.max().z() > 5.0);
+    }
+}
@@ -0,0 +1,177 @@
+use std::{cmp::Ordering, sync::Arc};
+
+use rand::Rng;
+
+use crate::{
+    aabb::{surrounding_box, Aabb},
+    hittable::{HitRecord, Hittable, HittableList, HittableObject},
+    ray::Ray,
+    rtweekend,
+};
+
+/// A node of a bounding volume hierarchy.
+///
+/// Can be used in place of a [`HittableList`] as the world, so that each
+/// ray only has to be tested against the objects whose boxes it crosses.
+pub struct BvhNode {
+    left: HittableObject,
+    right: HittableObject,
+    bbox: Aabb,
+}
+
+impl BvhNode {
+    /// Builds the hierarchy out of all the objects of the list, always
+    /// splitting along the same axes.
+    ///
+    /// # Panics
+    /// If the list is empty or one of the objects has no bounding box.
+    pub fn new(list: &HittableList) -> Self {
+        Self::with_rng(list, &mut rtweekend::stream_rng(0, rtweekend::BVH_STREAM))
+    }
+
+    /// Builds the hierarchy, picking the split axes with the generator.
+    ///
+    /// # Panics
+    /// If the list is empty or one of the objects has no bounding box.
+    pub fn with_rng<G: Rng + ?Sized>(list: &HittableList, rng: &mut G) -> Self {
+        let mut objects = list.objects().to_vec();
+        Self::from_objects(&mut objects, rng)
+    }
+
+    fn from_objects<G: Rng + ?Sized>(objects: &mut [HittableObject], rng: &mut G) -> Self {
+        assert!(!objects.is_empty(), "unable to build a bvh without objects");
+
+        let axis = rtweekend::rand_range(rng, 0..3);
+        let comparator = |a: &HittableObject, b: &HittableObject| box_compare(a, b, axis);
+
+        let (left, right): (HittableObject, HittableObject) = match objects.len() {
+            1 => (objects[0].clone(), objects[0].clone()),
+            2 => {
+                if comparator(&objects[0], &objects[1]) == Ordering::Greater {
+                    (objects[1].clone(), objects[0].clone())
+                } else {
+                    (objects[0].clone(), objects[1].clone())
+                }
+            }
+            len => {
+                objects.sort_by(comparator);
+
+                let (l, r) = objects.split_at_mut(len / 2);
+                (
+                    Arc::new(Self::from_objects(l, rng)),
+                    Arc::new(Self::from_objects(r, rng)),
+                )
+            }
+        };
+
+        let box_left = bounding_box_of(&left);
+        let box_right = bounding_box_of(&right);
+
+        Self {
+            left,
+            right,
+            bbox: surrounding_box(&box_left, &box_right),
+        }
+    }
+}
+
+fn bounding_box_of(obj: &HittableObject) -> Aabb {
+    let mut output_box = Aabb::default();
+    if !obj.bounding_box(&mut output_box) {
+        panic!("no bounding box in bvh node constructor");
+    }
+    output_box
+}
+
+fn box_compare(a: &HittableObject, b: &HittableObject, axis: usize) -> Ordering {
+    let box_a = bounding_box_of(a);
+    let box_b = bounding_box_of(b);
+
+    let a = box_a.min()[axis];
+    let b = box_b.min()[axis];
+
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return false;
+        }
+
+        let hit_left = self.left.hit(r, t_min, t_max, rec);
+        let hit_right = self
+            .right
+            .hit(r, t_min, if hit_left { rec.t } else { t_max }, rec);
+
+        hit_left || hit_right
+    }
+
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        *output_box = self.bbox;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        material::Lambartian,
+        ray::{Point, Vec3},
+        render::Color,
+        sphere::Sphere,
+    };
+
+    fn setup() -> HittableList {
+        let mut list = HittableList::new();
+        let mat = Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)));
+
+        for i in 0..10 {
+            for j in 0..10 {
+                let center = Point::new(i as f64, j as f64, 0.0);
+                list.add(Arc::new(Sphere::new(center, 0.25, mat.clone())));
+            }
+        }
+
+        list
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let list = setup();
+        let bvh = BvhNode::new(&list);
+
+        let mut bbox = Aabb::default();
+        assert!(bvh.bounding_box(&mut bbox));
+        assert_eq!(bbox.min(), Point::new(-0.25, -0.25, -0.25));
+        assert_eq!(bbox.max(), Point::new(9.25, 9.25, 0.25));
+    }
+
+    #[test]
+    fn test_same_as_list() {
+        let list = setup();
+        let bvh = BvhNode::new(&list);
+
+        for i in 0..10 {
+            for j in 0..10 {
+                let origin = Point::new(i as f64 * 0.95, j as f64 * 1.05, 5.0);
+                let r = Ray::new(origin, Vec3::new(0.01, -0.02, -1.0));
+
+                let mut rec_list = HitRecord::default();
+                let mut rec_bvh = HitRecord::default();
+
+                let hit_list = list.hit(&r, 0.001, f64::INFINITY, &mut rec_list);
+                let hit_bvh = bvh.hit(&r, 0.001, f64::INFINITY, &mut rec_bvh);
+
+                assert_eq!(hit_list, hit_bvh);
+                if hit_list {
+                    assert_eq!(rec_list.t, rec_bvh.t);
+                    assert_eq!(rec_list.p, rec_bvh.p);
+                }
+            }
+        }
+    }
+}
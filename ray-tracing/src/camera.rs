@@ -1,7 +1,9 @@
+use rand::Rng;
+
 use crate::{
-    cvec::cross,
     degrees_to_radians,
     ray::{Point, Ray, Vec3},
+    transform::Mat4,
 };
 
 pub struct Camera {
@@ -30,9 +32,10 @@ impl Camera {
         let viewport_height = 2.0 * h;
         let viewport_width = aspect_ratio * viewport_height;
 
-        let w = (lookfrom - lookat).unit_vector();
-        let u = cross(&vup, &w).unit_vector();
-        let v = cross(&w, &u);
+        // the rows of the view rotation are the camera axes in world space
+        let view = Mat4::look_at(lookfrom, lookat, vup);
+        let axis = |i: usize| Vec3::new(view[(i, 0)], view[(i, 1)], view[(i, 2)]);
+        let (u, v, w) = (axis(0), axis(1), axis(2));
 
         let origin = lookfrom;
         let horizontal = focus_dist * viewport_width * u;
@@ -52,8 +55,8 @@ impl Camera {
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * Vec3::random_in_unit_disk();
+    pub fn get_ray<G: Rng + ?Sized>(&self, s: f64, t: f64, rng: &mut G) -> Ray {
+        let rd = self.lens_radius * Vec3::random_in_unit_disk(rng);
         let offset = self.u * rd.x() + self.v * rd.y();
         Ray::new(
             self.origin + offset,
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    aarect::{XyRect, XzRect, YzRect},
+    hittable::{HitRecord, Hittable, HittableList},
+    material::Mat,
+    ray::{Point, Ray},
+};
+
+/// Minimum extent of the bounding box, for boxes that are flat.
+const BOX_PADDING: f64 = 1e-4;
+
+/// An axis aligned box made out of six rectangles, spanned by any two
+/// opposite corners.
+pub struct Cuboid {
+    box_min: Point,
+    box_max: Point,
+    sides: HittableList,
+}
+
+impl Cuboid {
+    pub fn new(p0: Point, p1: Point, mat: Mat) -> Self {
+        let mut sides = HittableList::with_capacity(6);
+
+        // the rectangles need their bounds in order
+        let (box_min, box_max) = (p0.min(&p1), p0.max(&p1));
+        let (x0, x1) = (box_min.x(), box_max.x());
+        let (y0, y1) = (box_min.y(), box_max.y());
+        let (z0, z1) = (box_min.z(), box_max.z());
+
+        sides.add(Arc::new(XyRect::new(x0, x1, y0, y1, z1, mat.clone())));
+        sides.add(Arc::new(XyRect::new(x0, x1, y0, y1, z0, mat.clone())));
+
+        sides.add(Arc::new(XzRect::new(x0, x1, z0, z1, y1, mat.clone())));
+        sides.add(Arc::new(XzRect::new(x0, x1, z0, z1, y0, mat.clone())));
+
+        sides.add(Arc::new(YzRect::new(y0, y1, z0, z1, x1, mat.clone())));
+        sides.add(Arc::new(YzRect::new(y0, y1, z0, z1, x0, mat)));
+
+        Self {
+            box_min,
+            box_max,
+            sides,
+        }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        self.sides.hit(r, t_min, t_max, rec)
+    }
+
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        *output_box = Aabb::new(self.box_min, self.box_max).padded(BOX_PADDING);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambartian, ray::Vec3, render::Color};
+
+    #[test]
+    fn test_hit() {
+        let mat = Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)));
+        let cuboid = Cuboid::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0, 2.0, 3.0), mat);
+        let mut rec = HitRecord::default();
+
+        let r = Ray::new(Point::new(0.5, 1.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(cuboid.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert_eq!(rec.t, 7.0);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(rec.front_face);
+
+        let inside = Ray::new(Point::new(0.5, 1.0, 1.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(cuboid.hit(&inside, 0.001, f64::INFINITY, &mut rec));
+        assert_eq!(rec.t, 0.5);
+        assert!(!rec.front_face);
+
+        // the same box from swapped corners
+        let mat = Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)));
+        let swapped = Cuboid::new(Point::new(1.0, 0.0, 3.0), Point::new(0.0, 2.0, 0.0), mat);
+        assert!(swapped.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert_eq!(rec.t, 7.0);
+
+        let mut bbox = Aabb::default();
+        assert!(swapped.bounding_box(&mut bbox));
+        assert_eq!(bbox.min(), Point::new(0.0, 0.0, 0.0));
+        assert_eq!(bbox.max(), Point::new(1.0, 2.0, 3.0));
+    }
+}
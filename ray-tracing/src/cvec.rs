@@ -1,9 +1,9 @@
-use std::{
-    ops::{self, Neg},
-    usize,
-};
+use std::ops::{self, Neg};
 
-use rand::distributions::uniform::{SampleRange, SampleUniform};
+use rand::{
+    distributions::uniform::{SampleRange, SampleUniform},
+    Rng,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct CVec<T, const N: usize>
@@ -39,28 +39,33 @@ where
     pub fn len(&self) -> usize {
         N
     }
+
+    pub fn is_empty(&self) -> bool {
+        N == 0
+    }
 }
 
 impl<T, const N: usize> CVec<T, N>
 where
     T: num_traits::NumRef + Neg<Output = T> + PartialOrd + SampleUniform + Default + Copy,
 {
-    pub fn random_range<R>(range: R) -> Self
+    pub fn random_range<R, G>(rng: &mut G, range: R) -> Self
     where
         R: SampleRange<T> + Clone,
+        G: Rng + ?Sized,
     {
         let mut data = [T::one(); N];
 
         for entry in data.as_mut() {
-            *entry = crate::rtweekend::rand_range(range.clone());
+            *entry = crate::rtweekend::rand_range(rng, range.clone());
         }
 
         Self { data }
     }
 
-    pub fn random_in_unit_sphere() -> Self {
+    pub fn random_in_unit_sphere<G: Rng + ?Sized>(rng: &mut G) -> Self {
         loop {
-            let p = Self::random_range(-T::one()..T::one());
+            let p = Self::random_range(rng, -T::one()..T::one());
 
             if p.length_squared() < T::one() {
                 return p;
@@ -120,12 +125,12 @@ where
         + Default
         + Copy,
 {
-    pub fn random_unit_vector() -> Self {
-        Self::random_in_unit_sphere().unit_vector()
+    pub fn random_unit_vector<G: Rng + ?Sized>(rng: &mut G) -> Self {
+        Self::random_in_unit_sphere(rng).unit_vector()
     }
 
-    pub fn random_in_hemisphere(&self) -> Self {
-        let in_unit = Self::random_in_unit_sphere();
+    pub fn random_in_hemisphere<G: Rng + ?Sized>(&self, rng: &mut G) -> Self {
+        let in_unit = Self::random_in_unit_sphere(rng);
         if dot(in_unit, *self) > T::zero() {
             in_unit
         } else {
@@ -142,6 +147,75 @@ where
     }
 }
 
+impl<T, const N: usize> ops::Index<usize> for CVec<T, N>
+where
+    T: Copy + Default,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.data[index]
+    }
+}
+
+impl<T, const N: usize> ops::IndexMut<usize> for CVec<T, N>
+where
+    T: Copy + Default,
+{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.data[index]
+    }
+}
+
+impl<T, const N: usize> CVec<T, N>
+where
+    T: PartialOrd + Default + Copy,
+{
+    /// Component-wise minimum.
+    pub fn min(&self, rhs: &Self) -> Self {
+        let mut next = self.data;
+
+        for (n, r) in next.iter_mut().zip(rhs.data.iter()) {
+            if *r < *n {
+                *n = *r;
+            }
+        }
+
+        next.into()
+    }
+
+    /// Component-wise maximum.
+    pub fn max(&self, rhs: &Self) -> Self {
+        let mut next = self.data;
+
+        for (n, r) in next.iter_mut().zip(rhs.data.iter()) {
+            if *r > *n {
+                *n = *r;
+            }
+        }
+
+        next.into()
+    }
+}
+
+impl<T, const N: usize> CVec<T, N>
+where
+    T: num_traits::NumRef + Neg<Output = T> + PartialOrd + Default + Copy,
+{
+    /// Component-wise absolute value.
+    pub fn abs(&self) -> Self {
+        let mut next = self.data;
+
+        for n in next.iter_mut() {
+            if *n < T::zero() {
+                *n = -*n;
+            }
+        }
+
+        next.into()
+    }
+}
+
 impl<T, const N: usize> CVec<T, N>
 where
     T: num_traits::NumRef + Default + Copy,
@@ -175,8 +249,8 @@ where
     fn add(self, rhs: Self) -> Self::Output {
         let mut next = [T::zero(); N];
 
-        for i in 0..self.len() {
-            next[i] = self.data[i] + rhs.data[i];
+        for (n, (l, r)) in next.iter_mut().zip(self.data.iter().zip(rhs.data.iter())) {
+            *n = *l + *r;
         }
 
         next.into()
@@ -192,8 +266,8 @@ where
     fn sub(self, rhs: Self) -> Self::Output {
         let mut next = [T::zero(); N];
 
-        for i in 0..self.len() {
-            next[i] = self.data[i] - rhs.data[i];
+        for (n, (l, r)) in next.iter_mut().zip(self.data.iter().zip(rhs.data.iter())) {
+            *n = *l - *r;
         }
 
         next.into()
@@ -220,8 +294,8 @@ where
     fn mul(self, rhs: Self) -> Self::Output {
         let mut next = [T::one(); N];
 
-        for i in 0..self.len() {
-            next[i] = self.data[i] * rhs.data[i];
+        for (n, (l, r)) in next.iter_mut().zip(self.data.iter().zip(rhs.data.iter())) {
+            *n = *l * *r;
         }
 
         next.into()
@@ -237,8 +311,8 @@ where
     fn mul(self, rhs: T) -> Self::Output {
         let mut next = [T::one(); N];
 
-        for i in 0..self.len() {
-            next[i] = self.data[i] * rhs;
+        for (n, l) in next.iter_mut().zip(self.data.iter()) {
+            *n = *l * rhs;
         }
 
         next.into()
@@ -284,6 +358,17 @@ where
     }
 }
 
+impl<T, const N: usize> ops::SubAssign for CVec<T, N>
+where
+    T: ops::SubAssign + Default + Copy,
+{
+    fn sub_assign(&mut self, rhs: Self) {
+        for i in 0..self.len() {
+            self.data[i] -= rhs.data[i];
+        }
+    }
+}
+
 impl<T, const N: usize> ops::MulAssign<T> for CVec<T, N>
 where
     T: ops::MulAssign + Default + Copy,
@@ -304,6 +389,24 @@ where
     }
 }
 
+impl<T, const N: usize> std::iter::Sum for CVec<T, N>
+where
+    T: num_traits::NumRef + Default + Copy,
+{
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold([T::zero(); N].into(), |acc, v| acc + v)
+    }
+}
+
+impl<'a, T, const N: usize> std::iter::Sum<&'a Self> for CVec<T, N>
+where
+    T: num_traits::NumRef + Default + Copy,
+{
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
 pub fn dot<T, const N: usize>(l: CVec<T, N>, r: CVec<T, N>) -> T
 where
     T: num_traits::NumRef + Default + Copy,
@@ -381,9 +484,9 @@ impl<T> Vec3<T>
 where
     T: num_traits::NumRef + Neg<Output = T> + PartialOrd + SampleUniform + Default + Copy,
 {
-    pub fn random_in_unit_disk() -> Self {
+    pub fn random_in_unit_disk<G: Rng + ?Sized>(rng: &mut G) -> Self {
         loop {
-            let mut p = Self::random_range(-T::one()..T::one());
+            let mut p = Self::random_range(rng, -T::one()..T::one());
             p.data[2] = T::zero();
 
             if p.length_squared() < T::one() {
@@ -505,6 +608,42 @@ mod tests {
         assert_eq!(v, r);
     }
 
+    #[test]
+    fn test_sub_assign() {
+        let (mut v, l) = setup();
+        let r: CVec<f64, 5> = [0.1 - 0.0, 0.2 - 0.1, 0.3 - 0.2, 0.4 - 0.3, 0.5 - 0.4].into();
+        v -= l;
+        assert_eq!(v, r);
+    }
+
+    #[test]
+    fn test_index() {
+        let (mut v, _) = setup();
+        assert_eq!(v[2], 0.3);
+
+        v[2] = 1.0;
+        assert_eq!(v, [0.1, 0.2, 1.0, 0.4, 0.5].into());
+    }
+
+    #[test]
+    fn test_min_max_abs() {
+        let v: CVec<f64, 3> = [-1.0, 2.0, -3.0].into();
+        let l: CVec<f64, 3> = [0.0, 1.0, -4.0].into();
+
+        assert_eq!(v.min(&l), [-1.0, 1.0, -4.0].into());
+        assert_eq!(v.max(&l), [0.0, 2.0, -3.0].into());
+        assert_eq!(v.abs(), [1.0, 2.0, 3.0].into());
+    }
+
+    #[test]
+    fn test_sum() {
+        let (v, l) = setup();
+        let r: CVec<f64, 5> = [0.1 + 0.0, 0.2 + 0.1, 0.3 + 0.2, 0.4 + 0.3, 0.5 + 0.4].into();
+
+        assert_eq!([v, l].iter().sum::<CVec<f64, 5>>(), r);
+        assert_eq!(vec![v, l].into_iter().sum::<CVec<f64, 5>>(), r);
+    }
+
     #[test]
     fn test_mul_assign_f64() {
         let (mut v, _) = setup();
@@ -1,6 +1,10 @@
+mod sah;
+pub use sah::*;
+
 use std::sync::Arc;
 
 use crate::{
+    aabb::{surrounding_box, Aabb},
     cvec::dot,
     material::Material,
     ray::{Point, Ray, Vec3},
@@ -12,6 +16,13 @@ pub struct HitRecord {
     pub normal: Vec3,
     pub mat: Option<Arc<dyn Material>>,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
+    /// The derivative of the point along `u`, zero if the surface has no
+    /// texture coordinates there.
+    pub tangent: Vec3,
+    /// The derivative of the point along `v`.
+    pub bitangent: Vec3,
     pub front_face: bool,
 }
 
@@ -29,10 +40,12 @@ impl HitRecord {
 
 pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool;
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool;
 }
 
 pub type HittableObject = Arc<dyn Hittable>;
 
+#[derive(Default)]
 pub struct HittableList {
     objects: Vec<HittableObject>,
 }
@@ -62,6 +75,14 @@ impl HittableList {
     pub fn add(&mut self, object: HittableObject) {
         self.objects.push(object);
     }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
 }
 
 impl Hittable for HittableList {
@@ -72,11 +93,35 @@ impl Hittable for HittableList {
         for obj in &self.objects {
             if obj.hit(r, t_min, closest_so_far, &mut temp_rec) {
                 hit_anything = true;
-                closest_so_far = temp_rec.t.clone();
+                closest_so_far = temp_rec.t;
                 *rec = temp_rec.clone();
             }
         }
 
         hit_anything
     }
+
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        if self.objects.is_empty() {
+            return false;
+        }
+
+        let mut temp_box = Aabb::default();
+        let mut first_box = true;
+
+        for obj in &self.objects {
+            if !obj.bounding_box(&mut temp_box) {
+                return false;
+            }
+
+            *output_box = if first_box {
+                temp_box
+            } else {
+                surrounding_box(output_box, &temp_box)
+            };
+            first_box = false;
+        }
+
+        true
+    }
 }
@@ -0,0 +1,444 @@
+use crate::{
+    aabb::{surrounding_box, Aabb},
+    hittable::{HitRecord, Hittable, HittableList, HittableObject},
+    ray::{Point, Ray},
+};
+
+/// Number of buckets the centroids get sorted into when evaluating the
+/// surface area heuristic.
+const BUCKETS: usize = 12;
+/// Relative cost of stepping through a node compared to testing an object.
+const TRAVERSAL_COST: f64 = 0.125;
+/// Past this depth the nodes are split by count, keeping the tree shallow
+/// enough for the fixed traversal stack.
+const MAX_SAH_DEPTH: usize = 32;
+const STACK_SIZE: usize = 64;
+
+pub const DEFAULT_MAX_LEAF_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+struct FlatNode {
+    bbox: Aabb,
+    /// Leaf: index of the first object. Interior: index of the second child,
+    /// the first one always directly follows its parent.
+    offset: usize,
+    /// Number of objects, zero for interior nodes.
+    count: usize,
+    /// Split axis of interior nodes.
+    axis: usize,
+}
+
+/// Statistics collected while building a [`SahBvh`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BvhStats {
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub max_depth: usize,
+    pub min_leaf_size: usize,
+    pub max_leaf_size: usize,
+    pub object_count: usize,
+}
+
+impl BvhStats {
+    pub fn avg_leaf_size(&self) -> f64 {
+        if self.leaf_count == 0 {
+            0.0
+        } else {
+            self.object_count as f64 / self.leaf_count as f64
+        }
+    }
+}
+
+/// A bounding volume hierarchy built using the surface area heuristic.
+///
+/// The nodes are stored depth first in one array, so traversal only
+/// follows indices instead of going through an `Arc` for every node.
+pub struct SahBvh {
+    objects: Vec<HittableObject>,
+    nodes: Vec<FlatNode>,
+    stats: BvhStats,
+}
+
+struct BuildObject {
+    index: usize,
+    bbox: Aabb,
+    centroid: Point,
+}
+
+impl SahBvh {
+    /// Builds the hierarchy out of all the objects of the list.
+    ///
+    /// # Panics
+    /// If one of the objects has no bounding box.
+    pub fn new(list: &HittableList) -> Self {
+        Self::with_max_leaf_size(list, DEFAULT_MAX_LEAF_SIZE)
+    }
+
+    pub fn with_max_leaf_size(list: &HittableList, max_leaf_size: usize) -> Self {
+        let max_leaf_size = max_leaf_size.max(1);
+
+        let mut build: Vec<_> = list
+            .objects()
+            .iter()
+            .enumerate()
+            .map(|(index, obj)| {
+                let mut bbox = Aabb::default();
+                if !obj.bounding_box(&mut bbox) {
+                    panic!("no bounding box in bvh constructor");
+                }
+                BuildObject {
+                    index,
+                    bbox,
+                    centroid: bbox.centroid(),
+                }
+            })
+            .collect();
+
+        let mut bvh = Self {
+            objects: Vec::with_capacity(build.len()),
+            nodes: Vec::with_capacity(2 * build.len()),
+            stats: BvhStats::default(),
+        };
+
+        if !build.is_empty() {
+            bvh.stats.min_leaf_size = usize::MAX;
+            bvh.build(list.objects(), &mut build, 0, max_leaf_size);
+        }
+
+        bvh
+    }
+
+    /// Get the statistics of the build.
+    pub fn stats(&self) -> BvhStats {
+        self.stats
+    }
+
+    fn build(
+        &mut self,
+        source: &[HittableObject],
+        build: &mut [BuildObject],
+        depth: usize,
+        max_leaf_size: usize,
+    ) {
+        let bbox = build
+            .iter()
+            .skip(1)
+            .fold(build[0].bbox, |acc, b| surrounding_box(&acc, &b.bbox));
+
+        let node_index = self.nodes.len();
+        self.nodes.push(FlatNode {
+            bbox,
+            offset: 0,
+            count: 0,
+            axis: 0,
+        });
+        self.stats.node_count += 1;
+        self.stats.max_depth = self.stats.max_depth.max(depth);
+
+        let split = if build.len() == 1 {
+            None
+        } else {
+            Self::find_split(build, &bbox, depth, max_leaf_size)
+        };
+
+        match split {
+            None => {
+                let node = &mut self.nodes[node_index];
+                node.offset = self.objects.len();
+                node.count = build.len();
+                self.objects
+                    .extend(build.iter().map(|b| source[b.index].clone()));
+
+                self.stats.leaf_count += 1;
+                self.stats.object_count += build.len();
+                self.stats.min_leaf_size = self.stats.min_leaf_size.min(build.len());
+                self.stats.max_leaf_size = self.stats.max_leaf_size.max(build.len());
+            }
+            Some((axis, mid)) => {
+                let (l, r) = build.split_at_mut(mid);
+                self.build(source, l, depth + 1, max_leaf_size);
+                self.nodes[node_index].offset = self.nodes.len();
+                self.nodes[node_index].axis = axis;
+                self.build(source, r, depth + 1, max_leaf_size);
+            }
+        }
+    }
+
+    /// Reorders the objects and returns the axis and the index they are
+    /// split at, or `None` if they should stay together in a leaf.
+    fn find_split(
+        build: &mut [BuildObject],
+        bbox: &Aabb,
+        depth: usize,
+        max_leaf_size: usize,
+    ) -> Option<(usize, usize)> {
+        let centroid_box = |b: &BuildObject| Aabb::new(b.centroid, b.centroid);
+        let cbox = build
+            .iter()
+            .skip(1)
+            .fold(centroid_box(&build[0]), |acc, b| {
+                surrounding_box(&acc, &centroid_box(b))
+            });
+
+        let axis = cbox.longest_axis();
+        let cmin = cbox.min()[axis];
+        let cmax = cbox.max()[axis];
+
+        // all centroids in the same spot, no split is going to help, but the
+        // leaves still can't get larger than allowed
+        if cmax <= cmin {
+            if build.len() <= max_leaf_size {
+                return None;
+            }
+            return Some((axis, build.len() / 2));
+        }
+
+        let by_axis = |a: &BuildObject, b: &BuildObject| {
+            a.centroid[axis]
+                .partial_cmp(&b.centroid[axis])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        };
+
+        if build.len() <= 2 || depth >= MAX_SAH_DEPTH {
+            let mid = build.len() / 2;
+            build.select_nth_unstable_by(mid, by_axis);
+            return Some((axis, mid));
+        }
+
+        let extent = cmax - cmin;
+        let bucket_of = |b: &BuildObject| {
+            let i = (BUCKETS as f64 * (b.centroid[axis] - cmin) / extent) as usize;
+            i.min(BUCKETS - 1)
+        };
+
+        let mut counts = [0usize; BUCKETS];
+        let mut boxes = [None::<Aabb>; BUCKETS];
+        for b in build.iter() {
+            let i = bucket_of(b);
+            counts[i] += 1;
+            boxes[i] = Some(match boxes[i] {
+                Some(ref bb) => surrounding_box(bb, &b.bbox),
+                None => b.bbox,
+            });
+        }
+
+        // cost of splitting after each of the buckets
+        let merge = |acc: (usize, Option<Aabb>), i: usize| {
+            let bbox = match (acc.1, boxes[i]) {
+                (Some(a), Some(b)) => Some(surrounding_box(&a, &b)),
+                (a, b) => a.or(b),
+            };
+            (acc.0 + counts[i], bbox)
+        };
+        let area = |b: Option<Aabb>| b.map_or(0.0, |b| b.surface_area());
+
+        let total_area = bbox.surface_area();
+        let (min_bucket, min_cost) = (0..BUCKETS - 1)
+            .map(|split| {
+                let (c0, b0) = (0..=split).fold((0, None), merge);
+                let (c1, b1) = (split + 1..BUCKETS).fold((0, None), merge);
+                let cost = if total_area > 0.0 {
+                    TRAVERSAL_COST + (c0 as f64 * area(b0) + c1 as f64 * area(b1)) / total_area
+                } else {
+                    TRAVERSAL_COST + build.len() as f64
+                };
+                (split, cost)
+            })
+            .fold(
+                (0, f64::INFINITY),
+                |best, cur| {
+                    if cur.1 < best.1 {
+                        cur
+                    } else {
+                        best
+                    }
+                },
+            );
+
+        let leaf_cost = build.len() as f64;
+        if build.len() <= max_leaf_size && min_cost >= leaf_cost {
+            return None;
+        }
+
+        let mid = partition(build, |b| bucket_of(b) <= min_bucket);
+        if mid == 0 || mid == build.len() {
+            let mid = build.len() / 2;
+            build.select_nth_unstable_by(mid, by_axis);
+            return Some((axis, mid));
+        }
+
+        Some((axis, mid))
+    }
+}
+
+/// Moves all the elements matching the predicate to the front and returns
+/// how many of them there are.
+fn partition<T, F: Fn(&T) -> bool>(data: &mut [T], pred: F) -> usize {
+    let mut first = 0;
+    for i in 0..data.len() {
+        if pred(&data[i]) {
+            data.swap(first, i);
+            first += 1;
+        }
+    }
+    first
+}
+
+impl Hittable for SahBvh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let dir = r.direction();
+        let dir_is_neg = [dir.x() < 0.0, dir.y() < 0.0, dir.z() < 0.0];
+
+        let mut temp_rec = Default::default();
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+
+        let mut stack = [0usize; STACK_SIZE];
+        let mut stack_len = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+
+            if node.bbox.hit(r, t_min, closest_so_far) {
+                if node.count > 0 {
+                    for obj in &self.objects[node.offset..node.offset + node.count] {
+                        if obj.hit(r, t_min, closest_so_far, &mut temp_rec) {
+                            hit_anything = true;
+                            closest_so_far = temp_rec.t;
+                            *rec = temp_rec.clone();
+                        }
+                    }
+                } else {
+                    // visit the child closer to the ray origin first
+                    let (near, far) = if dir_is_neg[node.axis] {
+                        (node.offset, current + 1)
+                    } else {
+                        (current + 1, node.offset)
+                    };
+                    stack[stack_len] = far;
+                    stack_len += 1;
+                    current = near;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            current = stack[stack_len];
+        }
+
+        hit_anything
+    }
+
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        match self.nodes.first() {
+            Some(node) => {
+                *output_box = node.bbox;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{material::Lambartian, ray::Vec3, render::Color, sphere::Sphere};
+
+    fn setup(n: usize) -> HittableList {
+        let mut list = HittableList::new();
+        let mat = Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)));
+
+        for i in 0..n {
+            for j in 0..n {
+                let center = Point::new(i as f64, j as f64, (i * j % 3) as f64);
+                list.add(Arc::new(Sphere::new(center, 0.25, mat.clone())));
+            }
+        }
+
+        list
+    }
+
+    #[test]
+    fn test_stats() {
+        let list = setup(16);
+        let bvh = SahBvh::new(&list);
+        let stats = bvh.stats();
+
+        assert_eq!(stats.object_count, 16 * 16);
+        assert_eq!(stats.node_count, 2 * stats.leaf_count - 1);
+        assert!(stats.min_leaf_size >= 1);
+        assert!(stats.max_leaf_size <= DEFAULT_MAX_LEAF_SIZE);
+        assert!(stats.max_depth < STACK_SIZE);
+    }
+
+    #[test]
+    fn test_same_centroids() {
+        let mut list = HittableList::new();
+        let mat = Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)));
+        for i in 0..20 {
+            let radius = 0.1 * (i + 1) as f64;
+            list.add(Arc::new(Sphere::new(
+                Point::new(1.0, 2.0, 3.0),
+                radius,
+                mat.clone(),
+            )));
+        }
+
+        let bvh = SahBvh::with_max_leaf_size(&list, 3);
+        let stats = bvh.stats();
+
+        assert_eq!(stats.object_count, 20);
+        assert!(stats.max_leaf_size <= 3, "{}", stats.max_leaf_size);
+
+        let r = Ray::new(Point::new(1.0, 2.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        assert!(bvh.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.t - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_empty() {
+        let bvh = SahBvh::new(&HittableList::new());
+        let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+
+        assert_eq!(bvh.stats(), BvhStats::default());
+        assert!(!bvh.hit(&r, 0.0, f64::INFINITY, &mut Default::default()));
+        assert!(!bvh.bounding_box(&mut Aabb::default()));
+    }
+
+    #[test]
+    fn test_same_as_list() {
+        let list = setup(10);
+        let bvh = SahBvh::new(&list);
+
+        for i in 0..20 {
+            for j in 0..20 {
+                let origin = Point::new(i as f64 * 0.5, j as f64 * 0.5, 5.0);
+                let r = Ray::new(origin, Vec3::new(0.03, -0.02, -1.0));
+
+                let mut rec_list = HitRecord::default();
+                let mut rec_bvh = HitRecord::default();
+
+                let hit_list = list.hit(&r, 0.001, f64::INFINITY, &mut rec_list);
+                let hit_bvh = bvh.hit(&r, 0.001, f64::INFINITY, &mut rec_bvh);
+
+                assert_eq!(hit_list, hit_bvh);
+                if hit_list {
+                    assert_eq!(rec_list.t, rec_bvh.t);
+                    assert_eq!(rec_list.p, rec_bvh.p);
+                }
+            }
+        }
+    }
+}
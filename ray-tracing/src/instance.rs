@@ -0,0 +1,157 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable, HittableObject},
+    ray::{Point, Ray, Vec3},
+    transform::{Quat, Transform},
+};
+
+/// Places an object in the world using an affine transform.
+///
+/// The transforms are applied in the order they are added, so
+/// `Instance::new(obj).scale(s).translate(t)` first scales, then moves.
+pub struct Instance {
+    object: HittableObject,
+    to_world: Transform,
+}
+
+impl Instance {
+    pub fn new(object: HittableObject) -> Self {
+        Self::with_transform(object, Transform::IDENTITY)
+    }
+
+    pub fn with_transform(object: HittableObject, to_world: Transform) -> Self {
+        Self { object, to_world }
+    }
+
+    /// Get the object to world transform of the instance.
+    pub fn transform(&self) -> &Transform {
+        &self.to_world
+    }
+
+    /// Appends the transform to the ones already applied.
+    pub fn then(mut self, transform: &Transform) -> Self {
+        self.to_world = transform.then(&self.to_world);
+        self
+    }
+
+    pub fn translate(self, offset: Vec3) -> Self {
+        self.then(&Transform::translation(offset))
+    }
+
+    /// Rotates counter clockwise around the axis through the origin.
+    pub fn rotate(self, axis: Vec3, degrees: f64) -> Self {
+        self.then(&Transform::rotation(axis, degrees))
+    }
+
+    pub fn rotate_quat(self, q: Quat) -> Self {
+        self.then(&q.into())
+    }
+
+    /// # Panics
+    /// If one of the factors is zero.
+    pub fn scale(self, factors: Vec3) -> Self {
+        self.then(&Transform::scaling(factors))
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        // the direction isn't normalized, so t is the same in both spaces
+        let to_object = self.to_world.inverse();
+        let moved = Ray::new(to_object.point(r.origin()), to_object.vector(r.direction()));
+
+        if !self.object.hit(&moved, t_min, t_max, rec) {
+            return false;
+        }
+
+        // the face orientation is kept by the inverse transpose
+        rec.p = self.to_world.point(rec.p);
+        rec.normal = self.to_world.normal(rec.normal).unit_vector();
+        rec.tangent = self.to_world.vector(rec.tangent);
+        rec.bitangent = self.to_world.vector(rec.bitangent);
+
+        true
+    }
+
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        let mut bbox = Aabb::default();
+        if !self.object.bounding_box(&mut bbox) {
+            return false;
+        }
+
+        let mut min = Point::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+
+        for i in 0..8 {
+            let pick = |bit, a: f64, b: f64| if i & bit == 0 { a } else { b };
+            let corner = self.to_world.point(Point::new(
+                pick(1, bbox.min().x(), bbox.max().x()),
+                pick(2, bbox.min().y(), bbox.max().y()),
+                pick(4, bbox.min().z(), bbox.max().z()),
+            ));
+
+            min = min.min(&corner);
+            max = max.max(&corner);
+        }
+
+        *output_box = Aabb::new(min, max);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{material::Lambartian, render::Color, sphere::Sphere};
+
+    fn sphere() -> HittableObject {
+        let mat = Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)));
+        Arc::new(Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0, mat))
+    }
+
+    fn assert_close(l: Vec3, r: Vec3) {
+        assert!((l - r).length() < 1e-9, "{:?} != {:?}", l, r);
+    }
+
+    #[test]
+    fn test_translate_rotate() {
+        let inst = Instance::new(sphere())
+            .translate(Vec3::new(2.0, 0.0, 0.0))
+            .rotate(Vec3::new(0.0, 0.0, 1.0), 90.0);
+        let mut rec = HitRecord::default();
+
+        // the sphere ends up at (0, 2, 0)
+        let r = Ray::new(Point::new(0.0, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(inst.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.t - 7.0).abs() < 1e-9);
+        assert_close(rec.p, Point::new(0.0, 3.0, 0.0));
+        assert_close(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+
+        let mut bbox = Aabb::default();
+        assert!(inst.bounding_box(&mut bbox));
+        assert_close(bbox.min(), Point::new(-1.0, 1.0, -1.0));
+        assert_close(bbox.max(), Point::new(1.0, 3.0, 1.0));
+    }
+
+    #[test]
+    fn test_scale() {
+        let inst = Instance::new(sphere()).scale(Vec3::new(2.0, 1.0, 1.0));
+        let mut rec = HitRecord::default();
+
+        let r = Ray::new(Point::new(10.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert!(inst.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.t - 8.0).abs() < 1e-9);
+        assert!(rec.front_face);
+
+        // on the ellipse x^2/4 + y^2 = 1 the normal is along (x/4, y)
+        let r = Ray::new(
+            Point::new(2.0f64.sqrt(), 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        );
+        assert!(inst.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        let expected = Vec3::new(2.0f64.sqrt() / 4.0, 0.5f64.sqrt(), 0.0).unit_vector();
+        assert_close(rec.normal, expected);
+    }
+}
@@ -0,0 +1,85 @@
+use rand::RngCore;
+
+use crate::{
+    hittable::Hittable,
+    ray::{Ray, Vec3},
+    render::Color,
+};
+
+/// Computes the light arriving along a ray.
+pub trait Integrator: Send + Sync {
+    fn ray_color(
+        &self,
+        r: &Ray,
+        world: &dyn Hittable,
+        depth: usize,
+        rng: &mut dyn RngCore,
+    ) -> Color;
+}
+
+/// What rays escaping the scene see.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Background {
+    /// A gradient from white at the horizon to blue at the top.
+    Sky,
+    Solid(Color),
+}
+
+impl Background {
+    pub fn color(&self, r: &Ray) -> Color {
+        match self {
+            Self::Sky => {
+                let unit_direction = r.direction().unit_vector();
+                let t = 0.5 * (unit_direction.y() + 1.0);
+                (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
+            }
+            Self::Solid(c) => *c,
+        }
+    }
+}
+
+/// A recursive path tracer following one scattered ray per bounce.
+pub struct PathTracer {
+    pub background: Background,
+}
+
+impl PathTracer {
+    pub fn new(background: Background) -> Self {
+        Self { background }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn ray_color(
+        &self,
+        r: &Ray,
+        world: &dyn Hittable,
+        depth: usize,
+        rng: &mut dyn RngCore,
+    ) -> Color {
+        if depth == 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let mut rec = Default::default();
+
+        if !world.hit(r, 0.001, f64::INFINITY, &mut rec) {
+            return self.background.color(r);
+        }
+
+        let mat = match rec.mat {
+            Some(ref mat) => mat,
+            None => return Color::new(0.0, 0.0, 0.0),
+        };
+
+        let mut scattered = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0));
+        let mut attenuation = Color::new(0.0, 0.0, 0.0);
+        let emitted = mat.emitted(rec.u, rec.v, &rec.p);
+
+        if mat.scatter(r, &rec, &mut attenuation, &mut scattered, rng) {
+            emitted + attenuation * self.ray_color(&scattered, world, depth - 1, rng)
+        } else {
+            emitted
+        }
+    }
+}
@@ -1,11 +1,26 @@
 pub mod render;
 
+pub mod aabb;
+pub mod aarect;
+pub mod bvh;
 pub mod camera;
+pub mod cuboid;
 mod cvec;
 pub mod hittable;
+pub mod instance;
+pub mod integrator;
+pub mod loader;
 pub mod material;
+pub mod math;
+pub mod microfacet;
+pub mod noise;
+pub mod plane;
 pub mod ray;
+pub mod renderer;
 pub mod sphere;
+pub mod texture;
+pub mod transform;
+pub mod triangle;
 
 mod rtweekend;
 pub use rtweekend::*;
@@ -0,0 +1,360 @@
+//! Loader for Wavefront `.obj` files.
+//!
+//! Supports positions, texture coordinates, normals, faces (n-gons are
+//! triangulated as fans) and groups. Everything else, such as materials and
+//! smoothing groups, is skipped.
+
+use std::{
+    collections::HashMap,
+    error, fmt,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+use crate::{
+    hittable::HittableList,
+    material::Mat,
+    ray::{Point, Vec3},
+    triangle::{TriangleMesh, Uv},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjErrorKind {
+    /// A value could not be parsed as a number.
+    InvalidNumber(String),
+    /// A statement has fewer values than it requires.
+    MissingValue(&'static str),
+    /// A face vertex is not of the form `v`, `v/vt`, `v//vn` or `v/vt/vn`.
+    InvalidFaceVertex(String),
+    /// An index is zero or points past the defined elements.
+    IndexOutOfRange(i64),
+    /// A face has fewer than three vertices.
+    DegenerateFace(usize),
+}
+
+impl fmt::Display for ObjErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidNumber(s) => write!(f, "invalid number '{}'", s),
+            Self::MissingValue(what) => write!(f, "missing {}", what),
+            Self::InvalidFaceVertex(s) => write!(f, "invalid face vertex '{}'", s),
+            Self::IndexOutOfRange(i) => write!(f, "index {} out of range", i),
+            Self::DegenerateFace(n) => write!(f, "face with only {} vertices", n),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    Parse { line: usize, kind: ObjErrorKind },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {}", err),
+            Self::Parse { line, kind } => write!(f, "line {}: {}", line, kind),
+        }
+    }
+}
+
+impl error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Parse { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ObjError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// A named group of faces.
+pub struct ObjGroup {
+    pub name: String,
+    pub mesh: TriangleMesh,
+}
+
+/// Indices into the position, texture coordinate and normal lists.
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+struct GroupBuilder {
+    name: String,
+    faces: Vec<[FaceVertex; 3]>,
+}
+
+impl GroupBuilder {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            faces: Vec::new(),
+        }
+    }
+
+    /// Turns the faces into a mesh with one vertex per distinct combination
+    /// of indices.
+    fn build(self, positions: &[Point], uvs: &[Uv], normals: &[Vec3], mat: &Mat) -> ObjGroup {
+        let all_uvs = self.faces.iter().flatten().all(|fv| fv.1.is_some());
+        let all_normals = self.faces.iter().flatten().all(|fv| fv.2.is_some());
+
+        let mut lookup = HashMap::new();
+        let mut mesh_positions = Vec::new();
+        let mut mesh_uvs = Vec::new();
+        let mut mesh_normals = Vec::new();
+
+        let indices = self
+            .faces
+            .iter()
+            .map(|face| {
+                let mut tri = [0; 3];
+                for (t, &(p, uv, n)) in tri.iter_mut().zip(face.iter()) {
+                    let key = (p, uv.filter(|_| all_uvs), n.filter(|_| all_normals));
+                    *t = *lookup.entry(key).or_insert_with(|| {
+                        mesh_positions.push(positions[p]);
+                        if let Some(uv) = key.1 {
+                            mesh_uvs.push(uvs[uv]);
+                        }
+                        if let Some(n) = key.2 {
+                            mesh_normals.push(normals[n]);
+                        }
+                        mesh_positions.len() - 1
+                    });
+                }
+                tri
+            })
+            .collect();
+
+        let mesh = TriangleMesh::new(
+            mesh_positions,
+            Some(mesh_normals).filter(|_| all_normals),
+            Some(mesh_uvs).filter(|_| all_uvs),
+            indices,
+            mat.clone(),
+        );
+
+        ObjGroup {
+            name: self.name,
+            mesh,
+        }
+    }
+}
+
+fn parse_f64(token: Option<&str>, what: &'static str) -> Result<f64, ObjErrorKind> {
+    let token = token.ok_or(ObjErrorKind::MissingValue(what))?;
+    token
+        .parse()
+        .map_err(|_| ObjErrorKind::InvalidNumber(token.to_string()))
+}
+
+/// Resolves a one based, or negative relative, index into a zero based one.
+fn resolve_index(token: &str, len: usize) -> Result<usize, ObjErrorKind> {
+    let idx: i64 = token
+        .parse()
+        .map_err(|_| ObjErrorKind::InvalidNumber(token.to_string()))?;
+
+    let resolved = if idx > 0 { idx - 1 } else { len as i64 + idx };
+
+    if idx == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(ObjErrorKind::IndexOutOfRange(idx));
+    }
+
+    Ok(resolved as usize)
+}
+
+fn parse_face_vertex(
+    token: &str,
+    positions: usize,
+    uvs: usize,
+    normals: usize,
+) -> Result<FaceVertex, ObjErrorKind> {
+    let invalid = || ObjErrorKind::InvalidFaceVertex(token.to_string());
+
+    let mut parts = token.split('/');
+    let p = parts.next().filter(|s| !s.is_empty()).ok_or_else(invalid)?;
+    let uv = parts.next().filter(|s| !s.is_empty());
+    let n = parts.next().filter(|s| !s.is_empty());
+
+    if parts.next().is_some() {
+        return Err(invalid());
+    }
+
+    Ok((
+        resolve_index(p, positions)?,
+        uv.map(|uv| resolve_index(uv, uvs)).transpose()?,
+        n.map(|n| resolve_index(n, normals)).transpose()?,
+    ))
+}
+
+/// Parses the obj data into its groups, all sharing the given material.
+///
+/// Faces before the first `g` or `o` statement end up in a group named
+/// `default`, empty groups are dropped.
+pub fn parse<R: BufRead>(reader: R, mat: Mat) -> Result<Vec<ObjGroup>, ObjError> {
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+
+    let mut builders = vec![GroupBuilder::new("default".to_string())];
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let err = |kind| ObjError::Parse { line: i + 1, kind };
+
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+
+        let keyword = match tokens.next() {
+            Some(k) => k,
+            None => continue,
+        };
+
+        match keyword {
+            "v" => {
+                let x = parse_f64(tokens.next(), "x coordinate").map_err(err)?;
+                let y = parse_f64(tokens.next(), "y coordinate").map_err(err)?;
+                let z = parse_f64(tokens.next(), "z coordinate").map_err(err)?;
+                positions.push(Point::new(x, y, z));
+            }
+            "vt" => {
+                let u = parse_f64(tokens.next(), "u coordinate").map_err(err)?;
+                let v = tokens
+                    .next()
+                    .map_or(Ok(0.0), |t| parse_f64(Some(t), "v coordinate"))
+                    .map_err(err)?;
+                uvs.push((u, v));
+            }
+            "vn" => {
+                let x = parse_f64(tokens.next(), "x component").map_err(err)?;
+                let y = parse_f64(tokens.next(), "y component").map_err(err)?;
+                let z = parse_f64(tokens.next(), "z component").map_err(err)?;
+                normals.push(Vec3::new(x, y, z));
+            }
+            "f" => {
+                let face = tokens
+                    .map(|t| parse_face_vertex(t, positions.len(), uvs.len(), normals.len()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(err)?;
+
+                if face.len() < 3 {
+                    return Err(err(ObjErrorKind::DegenerateFace(face.len())));
+                }
+
+                let group = builders.last_mut().expect("there is always a group");
+                for k in 1..face.len() - 1 {
+                    group.faces.push([face[0], face[k], face[k + 1]]);
+                }
+            }
+            "g" | "o" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                builders.push(GroupBuilder::new(name));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(builders
+        .into_iter()
+        .filter(|b| !b.faces.is_empty())
+        .map(|b| b.build(&positions, &uvs, &normals, &mat))
+        .collect())
+}
+
+/// Loads the obj file and returns all of its triangles.
+pub fn load<P: AsRef<Path>>(path: P, mat: Mat) -> Result<HittableList, ObjError> {
+    let file = File::open(path)?;
+    let groups = parse(BufReader::new(file), mat)?;
+
+    let mut list = HittableList::new();
+    for group in groups {
+        for obj in group.mesh.into_list().objects() {
+            list.add(obj.clone());
+        }
+    }
+
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{material::Lambartian, render::Color};
+
+    fn mat() -> Mat {
+        Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn test_parse() -> Result<(), ObjError> {
+        let data = "\
+# a quad and a triangle
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+
+f 1/1/1 2/2/1 3/3/1 4/4/1
+g second part
+f -4 -3 -2
+";
+
+        let groups = parse(data.as_bytes(), mat())?;
+        assert_eq!(groups.len(), 2);
+
+        let quad = &groups[0];
+        assert_eq!(quad.name, "default");
+        assert_eq!(quad.mesh.indices(), &[[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(quad.mesh.uvs().map(|uv| uv.len()), Some(4));
+        assert_eq!(quad.mesh.normals().map(|n| n.len()), Some(4));
+
+        let tri = &groups[1];
+        assert_eq!(tri.name, "second part");
+        assert_eq!(tri.mesh.len(), 1);
+        assert_eq!(tri.mesh.positions()[2], Point::new(1.0, 1.0, 0.0));
+        assert!(tri.mesh.uvs().is_none());
+        assert!(tri.mesh.normals().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() {
+        let line_kind = |data: &str| match parse(data.as_bytes(), mat()) {
+            Err(ObjError::Parse { line, kind }) => Some((line, kind)),
+            _ => None,
+        };
+
+        assert_eq!(
+            line_kind("v 0 0 0\nv 1 x 0\n"),
+            Some((2, ObjErrorKind::InvalidNumber("x".to_string())))
+        );
+        assert_eq!(
+            line_kind("v 0 0\n"),
+            Some((1, ObjErrorKind::MissingValue("z coordinate")))
+        );
+        assert_eq!(
+            line_kind("v 0 0 0\n\nf 1 2 3\n"),
+            Some((3, ObjErrorKind::IndexOutOfRange(2)))
+        );
+        assert_eq!(
+            line_kind("v 0 0 0\nv 1 0 0\nf 1 2\n"),
+            Some((3, ObjErrorKind::DegenerateFace(2)))
+        );
+        assert_eq!(
+            line_kind("v 0 0 0\nf 1/1/1/1 1 1\n"),
+            Some((2, ObjErrorKind::InvalidFaceVertex("1/1/1/1".to_string())))
+        );
+    }
+}
@@ -0,0 +1,540 @@
+//! Loader for `.ply` files in the ascii and binary encodings.
+//!
+//! Reads the `vertex` element (positions with optional normals and texture
+//! coordinates) and the `vertex_indices` list of the `face` element.
+//! Polygons are triangulated as fans, all other elements are skipped.
+
+use std::{
+    collections::VecDeque,
+    error, fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+};
+
+use crate::{
+    hittable::HittableList,
+    material::Mat,
+    ray::{Point, Vec3},
+    triangle::TriangleMesh,
+};
+
+#[derive(Debug)]
+pub enum PlyError {
+    Io(io::Error),
+    /// The header is malformed, contains the offending line.
+    InvalidHeader(String),
+    UnsupportedFormat(String),
+    UnsupportedPropertyType(String),
+    /// A property required to build the mesh is missing.
+    MissingProperty(&'static str),
+    /// A value in the ascii body could not be parsed.
+    InvalidValue(String),
+    IndexOutOfRange(i64),
+    /// A face has fewer than three vertices.
+    DegenerateFace(usize),
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {}", err),
+            Self::InvalidHeader(line) => write!(f, "invalid header line '{}'", line),
+            Self::UnsupportedFormat(s) => write!(f, "unsupported format '{}'", s),
+            Self::UnsupportedPropertyType(s) => write!(f, "unsupported property type '{}'", s),
+            Self::MissingProperty(s) => write!(f, "missing property '{}'", s),
+            Self::InvalidValue(s) => write!(f, "invalid value '{}'", s),
+            Self::IndexOutOfRange(i) => write!(f, "vertex index {} out of range", i),
+            Self::DegenerateFace(n) => write!(f, "face with only {} vertices", n),
+        }
+    }
+}
+
+impl error::Error for PlyError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PlyError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(s: &str) -> Result<Self, PlyError> {
+        Ok(match s {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => return Err(PlyError::UnsupportedPropertyType(s.to_string())),
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(String, Scalar),
+    List(String, Scalar, Scalar),
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Self::Scalar(name, _) | Self::List(name, _, _) => name,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn find(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|p| names.contains(&p.name()))
+    }
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+fn read_header<R: BufRead>(reader: &mut R) -> Result<Header, PlyError> {
+    let mut line = String::new();
+    let mut next_line = |reader: &mut R| -> Result<String, PlyError> {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(line.trim().to_string())
+    };
+
+    let magic = next_line(reader)?;
+    if magic != "ply" {
+        return Err(PlyError::InvalidHeader(magic));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    loop {
+        let line = next_line(reader)?;
+        let tokens: Vec<_> = line.split_whitespace().collect();
+        let invalid = || PlyError::InvalidHeader(line.clone());
+
+        match tokens.as_slice() {
+            ["end_header"] => break,
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            ["format", f, _version] => {
+                format = Some(match *f {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(PlyError::UnsupportedFormat(f.to_string())),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| invalid())?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or_else(invalid)?
+                .properties
+                .push(Property::List(
+                    name.to_string(),
+                    Scalar::parse(count)?,
+                    Scalar::parse(item)?,
+                )),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or_else(invalid)?
+                .properties
+                .push(Property::Scalar(name.to_string(), Scalar::parse(ty)?)),
+            _ => return Err(invalid()),
+        }
+    }
+
+    Ok(Header {
+        format: format.ok_or(PlyError::MissingProperty("format"))?,
+        elements,
+    })
+}
+
+/// Source of the values of the body, independent of the encoding.
+trait Values {
+    fn next(&mut self, ty: Scalar) -> Result<f64, PlyError>;
+}
+
+struct Ascii<R> {
+    reader: R,
+    tokens: VecDeque<String>,
+}
+
+impl<R: BufRead> Values for Ascii<R> {
+    fn next(&mut self, _ty: Scalar) -> Result<f64, PlyError> {
+        while self.tokens.is_empty() {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            self.tokens
+                .extend(line.split_whitespace().map(str::to_string));
+        }
+
+        let token = self
+            .tokens
+            .pop_front()
+            .expect("the loop above reads a token");
+        token.parse().map_err(|_| PlyError::InvalidValue(token))
+    }
+}
+
+struct Binary<R> {
+    reader: R,
+    big_endian: bool,
+}
+
+macro_rules! read_binary {
+    ($self:ident, $t:ty) => {{
+        let mut buf = [0u8; std::mem::size_of::<$t>()];
+        $self.reader.read_exact(&mut buf)?;
+        if $self.big_endian {
+            <$t>::from_be_bytes(buf) as f64
+        } else {
+            <$t>::from_le_bytes(buf) as f64
+        }
+    }};
+}
+
+impl<R: Read> Values for Binary<R> {
+    fn next(&mut self, ty: Scalar) -> Result<f64, PlyError> {
+        Ok(match ty {
+            Scalar::I8 => read_binary!(self, i8),
+            Scalar::U8 => read_binary!(self, u8),
+            Scalar::I16 => read_binary!(self, i16),
+            Scalar::U16 => read_binary!(self, u16),
+            Scalar::I32 => read_binary!(self, i32),
+            Scalar::U32 => read_binary!(self, u32),
+            Scalar::F32 => read_binary!(self, f32),
+            Scalar::F64 => read_binary!(self, f64),
+        })
+    }
+}
+
+/// Reads one instance of the element, every property into its own entry of
+/// `row`.
+fn read_row<V: Values>(
+    values: &mut V,
+    element: &Element,
+    row: &mut [Vec<f64>],
+) -> Result<(), PlyError> {
+    for (prop, out) in element.properties.iter().zip(row.iter_mut()) {
+        out.clear();
+        match *prop {
+            Property::Scalar(_, ty) => out.push(values.next(ty)?),
+            Property::List(_, count, item) => {
+                let count = values.next(count)?;
+                if count < 0.0 {
+                    return Err(PlyError::InvalidValue(count.to_string()));
+                }
+                for _ in 0..count as usize {
+                    out.push(values.next(item)?);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn read_body<V: Values>(
+    values: &mut V,
+    header: &Header,
+    mat: Mat,
+) -> Result<TriangleMesh, PlyError> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut indices = Vec::new();
+
+    for element in &header.elements {
+        let mut row = vec![Vec::new(); element.properties.len()];
+
+        match element.name.as_str() {
+            "vertex" => {
+                let find = |name: &'static str| {
+                    element.find(&[name]).ok_or(PlyError::MissingProperty(name))
+                };
+                let pos = [find("x")?, find("y")?, find("z")?];
+                let normal = match (find("nx"), find("ny"), find("nz")) {
+                    (Ok(x), Ok(y), Ok(z)) => Some([x, y, z]),
+                    _ => None,
+                };
+                let uv = match (
+                    element.find(&["u", "s", "texture_u"]),
+                    element.find(&["v", "t", "texture_v"]),
+                ) {
+                    (Some(u), Some(v)) => Some([u, v]),
+                    _ => None,
+                };
+
+                for _ in 0..element.count {
+                    read_row(values, element, &mut row)?;
+                    let get = |i: usize| row[i].first().copied().unwrap_or_default();
+
+                    positions.push(Point::new(get(pos[0]), get(pos[1]), get(pos[2])));
+                    if let Some(n) = normal {
+                        normals.push(Vec3::new(get(n[0]), get(n[1]), get(n[2])));
+                    }
+                    if let Some(uv) = uv {
+                        uvs.push((get(uv[0]), get(uv[1])));
+                    }
+                }
+            }
+            "face" => {
+                let list = element
+                    .find(&["vertex_indices", "vertex_index"])
+                    .ok_or(PlyError::MissingProperty("vertex_indices"))?;
+
+                for _ in 0..element.count {
+                    read_row(values, element, &mut row)?;
+                    let face = &row[list];
+
+                    if face.len() < 3 {
+                        return Err(PlyError::DegenerateFace(face.len()));
+                    }
+
+                    let face: Vec<_> = face.iter().map(|&i| i as i64).collect();
+                    for k in 1..face.len() - 1 {
+                        indices.push([face[0], face[k], face[k + 1]]);
+                    }
+                }
+            }
+            _ => {
+                for _ in 0..element.count {
+                    read_row(values, element, &mut row)?;
+                }
+            }
+        }
+    }
+
+    let indices = indices
+        .into_iter()
+        .map(|tri| {
+            let mut res = [0; 3];
+            for (r, i) in res.iter_mut().zip(tri) {
+                if i < 0 || i as usize >= positions.len() {
+                    return Err(PlyError::IndexOutOfRange(i));
+                }
+                *r = i as usize;
+            }
+            Ok(res)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let normals = Some(normals).filter(|n| !n.is_empty());
+    let uvs = Some(uvs).filter(|uv| !uv.is_empty());
+
+    Ok(TriangleMesh::new(positions, normals, uvs, indices, mat))
+}
+
+/// Parses the ply data into a mesh with the given material.
+pub fn parse<R: BufRead>(mut reader: R, mat: Mat) -> Result<TriangleMesh, PlyError> {
+    let header = read_header(&mut reader)?;
+
+    match header.format {
+        Format::Ascii => read_body(
+            &mut Ascii {
+                reader,
+                tokens: VecDeque::new(),
+            },
+            &header,
+            mat,
+        ),
+        Format::BinaryLittleEndian | Format::BinaryBigEndian => read_body(
+            &mut Binary {
+                reader,
+                big_endian: header.format == Format::BinaryBigEndian,
+            },
+            &header,
+            mat,
+        ),
+    }
+}
+
+/// Loads the ply file and returns all of its triangles.
+pub fn load<P: AsRef<Path>>(path: P, mat: Mat) -> Result<HittableList, PlyError> {
+    let file = File::open(path)?;
+    Ok(parse(BufReader::new(file), mat)?.into_list())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{material::Lambartian, render::Color};
+
+    fn mat() -> Mat {
+        Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    const POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+
+    fn binary(format: &str, big_endian: bool) -> Vec<u8> {
+        let mut data = format!(
+            "ply\nformat {} 1.0\nelement vertex 4\n\
+             property float x\nproperty float y\nproperty float z\n\
+             element face 1\nproperty list uchar int vertex_indices\nend_header\n",
+            format
+        )
+        .into_bytes();
+
+        for p in POSITIONS.iter().flatten() {
+            let bytes = if big_endian {
+                p.to_be_bytes()
+            } else {
+                p.to_le_bytes()
+            };
+            data.extend_from_slice(&bytes);
+        }
+
+        data.push(4);
+        for i in 0..4i32 {
+            let bytes = if big_endian {
+                i.to_be_bytes()
+            } else {
+                i.to_le_bytes()
+            };
+            data.extend_from_slice(&bytes);
+        }
+
+        data
+    }
+
+    fn check_quad(mesh: &TriangleMesh) {
+        let positions: Vec<_> = POSITIONS
+            .iter()
+            .map(|p| Point::new(p[0] as f64, p[1] as f64, p[2] as f64))
+            .collect();
+        assert_eq!(mesh.positions(), positions.as_slice());
+        assert_eq!(mesh.indices(), &[[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn test_ascii() -> Result<(), PlyError> {
+        let data = "\
+ply
+format ascii 1.0
+comment a unit quad
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property float s
+property float t
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 1 0 0
+1 0 0 0 0 1 1 0
+1 1 0 0 0 1 1 1
+0 1 0 0 0 1 0 1
+4 0 1 2 3
+";
+        let mesh = parse(data.as_bytes(), mat())?;
+        check_quad(&mesh);
+        assert_eq!(mesh.normals().map(|n| n[0]), Some(Vec3::new(0.0, 0.0, 1.0)));
+        assert_eq!(mesh.uvs().map(|uv| uv[2]), Some((1.0, 1.0)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary() -> Result<(), PlyError> {
+        let le = parse(binary("binary_little_endian", false).as_slice(), mat())?;
+        check_quad(&le);
+        assert!(le.normals().is_none());
+        assert!(le.uvs().is_none());
+
+        let be = parse(binary("binary_big_endian", true).as_slice(), mat())?;
+        check_quad(&be);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() {
+        let header = |body: &str| format!("ply\nformat ascii 1.0\n{}end_header\n", body);
+
+        let res = parse(
+            header("element vertex 0\nproperty half x\n").as_bytes(),
+            mat(),
+        );
+        assert!(matches!(res, Err(PlyError::UnsupportedPropertyType(t)) if t == "half"));
+
+        let res = parse(
+            header("element vertex 0\nproperty float x\n").as_bytes(),
+            mat(),
+        );
+        assert!(matches!(res, Err(PlyError::MissingProperty("y"))));
+
+        let res = parse(
+            "ply\nformat binary_middle_endian 1.0\nend_header\n".as_bytes(),
+            mat(),
+        );
+        assert!(matches!(res, Err(PlyError::UnsupportedFormat(_))));
+
+        let data = header(
+            "element vertex 1\nproperty float x\nproperty float y\nproperty float z\n\
+             element face 1\nproperty list uchar int vertex_indices\n",
+        ) + "0 0 0\n3 0 1 2\n";
+        let res = parse(data.as_bytes(), mat());
+        assert!(matches!(res, Err(PlyError::IndexOutOfRange(1))));
+    }
+}
@@ -1,11 +1,14 @@
 use std::sync::Arc;
 
+use rand::RngCore;
+
 use crate::{
-    cvec::{self, dot, reflect, refract},
+    cvec::{self, cross, dot, reflect, refract},
     hittable::HitRecord,
-    ray::{Ray, Vec3},
-    render::Color,
+    ray::{Point, Ray, Vec3},
+    render::{tonemap::luminance, Color},
     rtweekend,
+    texture::{SolidColor, Tex},
 };
 
 pub type Mat = Arc<dyn Material>;
@@ -17,16 +20,26 @@ pub trait Material: Send + Sync {
         rec: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        rng: &mut dyn RngCore,
     ) -> bool;
+
+    /// Light given off by the surface, black for anything but lights.
+    fn emitted(&self, _u: f64, _v: f64, _p: &Point) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
 }
 
 pub struct Lambartian {
-    pub albedo: Color,
+    pub albedo: Tex,
 }
 
 impl Lambartian {
     pub fn new(a: Color) -> Self {
-        Self { albedo: a }
+        Self::with_texture(Arc::new(SolidColor::new(a)))
+    }
+
+    pub fn with_texture(albedo: Tex) -> Self {
+        Self { albedo }
     }
 }
 
@@ -37,26 +50,31 @@ impl Material for Lambartian {
         rec: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        rng: &mut dyn RngCore,
     ) -> bool {
-        let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
+        let mut scatter_direction = rec.normal + Vec3::random_unit_vector(rng);
         if scatter_direction.near_zero() {
             scatter_direction = rec.normal;
         }
         *scattered = Ray::new(rec.p, scatter_direction);
-        *attenuation = self.albedo;
+        *attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
 
         true
     }
 }
 
 pub struct Metal {
-    pub albedo: Color,
+    pub albedo: Tex,
     pub fuzz: f64,
 }
 
 impl Metal {
     pub fn new(a: Color, f: f64) -> Self {
-        Self { albedo: a, fuzz: f }
+        Self::with_texture(Arc::new(SolidColor::new(a)), f)
+    }
+
+    pub fn with_texture(albedo: Tex, fuzz: f64) -> Self {
+        Self { albedo, fuzz }
     }
 }
 
@@ -67,10 +85,14 @@ impl Material for Metal {
         rec: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        rng: &mut dyn RngCore,
     ) -> bool {
         let reflected = cvec::reflect(r_in.direction().unit_vector(), rec.normal);
-        *scattered = Ray::new(rec.p, reflected + self.fuzz * Vec3::random_in_unit_sphere());
-        *attenuation = self.albedo;
+        *scattered = Ray::new(
+            rec.p,
+            reflected + self.fuzz * Vec3::random_in_unit_sphere(rng),
+        );
+        *attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
         cvec::dot(scattered.direction(), rec.normal) > 0.0
     }
 }
@@ -99,6 +121,7 @@ impl Material for Dielectric {
         rec: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        rng: &mut dyn RngCore,
     ) -> bool {
         *attenuation = Color::new(1.0, 1.0, 1.0);
         let refraction_ratio = if rec.front_face {
@@ -114,7 +137,7 @@ impl Material for Dielectric {
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
 
         let direction = if cannot_refract
-            || Self::reflectance(cos_theta, refraction_ratio) > rtweekend::rand_range(0.0..1.0)
+            || Self::reflectance(cos_theta, refraction_ratio) > rtweekend::rand_range(rng, 0.0..1.0)
         {
             reflect(unit_direction, rec.normal)
         } else {
@@ -126,3 +149,318 @@ impl Material for Dielectric {
         true
     }
 }
+
+/// A surface emitting light in all directions.
+pub struct DiffuseLight {
+    pub emit: Tex,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self::with_texture(Arc::new(SolidColor::new(emit)))
+    }
+
+    pub fn with_texture(emit: Tex) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+        _attenuation: &mut Color,
+        _scattered: &mut Ray,
+        _rng: &mut dyn RngCore,
+    ) -> bool {
+        false
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Point) -> Color {
+        self.emit.value(u, v, p)
+    }
+}
+
+/// An orthonormal tangent and bitangent around the normal, following the
+/// derivatives of the surface where there are any.
+pub(crate) fn tangent_frame(normal: Vec3, dpdu: Vec3, dpdv: Vec3) -> (Vec3, Vec3) {
+    let mut tangent = dpdu - dot(dpdu, normal) * normal;
+    if tangent.near_zero() {
+        // no usable derivative, any direction will do
+        let helper = if normal.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        tangent = cross(&helper, &normal);
+    }
+    let tangent = tangent.unit_vector();
+
+    // mirrored texture coordinates flip the bitangent
+    let bitangent = cross(&normal, &tangent);
+    if dot(bitangent, dpdv) < 0.0 {
+        (tangent, -bitangent)
+    } else {
+        (tangent, bitangent)
+    }
+}
+
+/// Replaces the normal of the hit record, the function gets and returns the
+/// normal on the outside of the surface.
+fn with_normal<F: Fn(Vec3) -> Vec3>(rec: &HitRecord, f: F) -> HitRecord {
+    let outward = if rec.front_face {
+        rec.normal
+    } else {
+        -rec.normal
+    };
+    let normal = f(outward).unit_vector();
+
+    let mut rec = rec.clone();
+    rec.normal = if rec.front_face { normal } else { -normal };
+    rec
+}
+
+/// Perturbs the normal with a tangent space normal map before handing the
+/// hit to the wrapped material.
+///
+/// The red, green and blue channels map `0..1` to `-1..1` along the tangent,
+/// the bitangent and the normal, so the map has to be linear.
+pub struct NormalMap {
+    pub inner: Mat,
+    pub map: Tex,
+    /// Scales the deviation from the surface normal.
+    pub strength: f64,
+}
+
+impl NormalMap {
+    pub fn new(inner: Mat, map: Tex) -> Self {
+        Self {
+            inner,
+            map,
+            strength: 1.0,
+        }
+    }
+
+    pub fn with_strength(mut self, strength: f64) -> Self {
+        self.strength = strength;
+        self
+    }
+
+    fn perturb(&self, rec: &HitRecord) -> HitRecord {
+        with_normal(rec, |n| {
+            let (t, b) = tangent_frame(n, rec.tangent, rec.bitangent);
+            let m = 2.0 * self.map.value(rec.u, rec.v, &rec.p) - Color::new(1.0, 1.0, 1.0);
+
+            self.strength * (m.x() * t + m.y() * b) + m.z() * n
+        })
+    }
+}
+
+impl Material for NormalMap {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        self.inner
+            .scatter(r_in, &self.perturb(rec), attenuation, scattered, rng)
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Point) -> Color {
+        self.inner.emitted(u, v, p)
+    }
+}
+
+/// Perturbs the normal by the slope of a height field before handing the
+/// hit to the wrapped material.
+///
+/// The height is the luminance of the texture, it is differentiated with
+/// finite differences along the texture coordinates.
+pub struct BumpMap {
+    pub inner: Mat,
+    pub height: Tex,
+    /// The height of a texture value of one, in the units of the scene.
+    pub scale: f64,
+    /// The step in `u` and `v` for the differences.
+    pub delta: f64,
+}
+
+impl BumpMap {
+    pub fn new(inner: Mat, height: Tex, scale: f64) -> Self {
+        Self {
+            inner,
+            height,
+            scale,
+            delta: 1.0 / 1024.0,
+        }
+    }
+
+    pub fn with_delta(mut self, delta: f64) -> Self {
+        self.delta = delta;
+        self
+    }
+
+    fn perturb(&self, rec: &HitRecord) -> HitRecord {
+        let height = |du: f64, dv: f64| {
+            // the point moves along as well, for the solid textures
+            let p = rec.p + du * rec.tangent + dv * rec.bitangent;
+            self.scale * luminance(self.height.value(rec.u + du, rec.v + dv, &p))
+        };
+
+        let d = self.delta;
+        let h = height(0.0, 0.0);
+        let dhdu = (height(d, 0.0) - h) / d;
+        let dhdv = (height(0.0, d) - h) / d;
+
+        // the slope of the height per unit of distance on the surface
+        let slope = |dh: f64, dp: Vec3| {
+            let len = dp.length();
+            if len > 0.0 {
+                dh / len
+            } else {
+                0.0
+            }
+        };
+
+        with_normal(rec, |n| {
+            let (t, b) = tangent_frame(n, rec.tangent, rec.bitangent);
+            n - slope(dhdu, rec.tangent) * t - slope(dhdv, rec.bitangent) * b
+        })
+    }
+}
+
+impl Material for BumpMap {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        self.inner
+            .scatter(r_in, &self.perturb(rec), attenuation, scattered, rng)
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Point) -> Color {
+        self.inner.emitted(u, v, p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::Checker;
+
+    #[test]
+    fn test_emitted() {
+        let p = Point::new(0.0, 0.0, 0.0);
+        let light = DiffuseLight::new(Color::new(4.0, 4.0, 4.0));
+        let lam = Lambartian::new(Color::new(0.5, 0.5, 0.5));
+
+        assert_eq!(light.emitted(0.0, 0.0, &p), Color::new(4.0, 4.0, 4.0));
+        assert_eq!(lam.emitted(0.0, 0.0, &p), Color::new(0.0, 0.0, 0.0));
+
+        let r = Ray::new(Point::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut attenuation = Color::default();
+        let mut scattered = Ray::new(p, p);
+        let mut rng = rtweekend::stream_rng(0, 0);
+        assert!(!light.scatter(
+            &r,
+            &HitRecord::default(),
+            &mut attenuation,
+            &mut scattered,
+            &mut rng
+        ));
+    }
+
+    #[test]
+    fn test_texture_albedo() {
+        let checker = Checker::with_colors(Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0))
+            .with_frequency(1.0);
+        let lam = Lambartian::with_texture(Arc::new(checker));
+
+        let r = Ray::new(Point::new(0.0, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut rec = HitRecord::default();
+        let mut attenuation = Color::default();
+        let mut scattered = Ray::new(rec.p, rec.normal);
+        let mut rng = rtweekend::stream_rng(0, 0);
+
+        rec.normal = Vec3::new(0.0, 1.0, 0.0);
+        rec.p = Point::new(1.0, 1.0, 1.0);
+        assert!(lam.scatter(&r, &rec, &mut attenuation, &mut scattered, &mut rng));
+        assert_eq!(attenuation, Color::new(1.0, 1.0, 1.0));
+
+        rec.p = Point::new(-1.0, 1.0, 1.0);
+        assert!(lam.scatter(&r, &rec, &mut attenuation, &mut scattered, &mut rng));
+        assert_eq!(attenuation, Color::new(0.0, 0.0, 0.0));
+    }
+
+    fn flat_hit(front_face: bool) -> HitRecord {
+        HitRecord {
+            normal: Vec3::new(0.0, 0.0, if front_face { 1.0 } else { -1.0 }),
+            front_face,
+            u: 0.5,
+            v: 0.5,
+            tangent: Vec3::new(2.0, 0.0, 0.0),
+            bitangent: Vec3::new(0.0, 2.0, 0.0),
+            ..HitRecord::default()
+        }
+    }
+
+    #[test]
+    fn test_tangent_frame() {
+        let n = Vec3::new(0.0, 0.0, 1.0);
+        let (t, b) = tangent_frame(n, Vec3::new(1.0, 0.0, 0.5), Vec3::new(0.0, -3.0, 0.0));
+        assert_eq!(t, Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(b, Vec3::new(0.0, -1.0, 0.0));
+
+        let (t, b) = tangent_frame(n, Vec3::default(), Vec3::default());
+        assert!(dot(t, n).abs() < 1e-12 && dot(b, n).abs() < 1e-12);
+        assert!((t.length() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_normal_map() {
+        let inner: Mat = Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)));
+
+        // a flat map keeps the normal
+        let flat = NormalMap::new(
+            inner.clone(),
+            Arc::new(SolidColor::new(Color::new(0.5, 0.5, 1.0))),
+        );
+        assert_eq!(
+            flat.perturb(&flat_hit(true)).normal,
+            Vec3::new(0.0, 0.0, 1.0)
+        );
+
+        // tilted towards the tangent, on either side of the surface
+        let tilted = NormalMap::new(inner, Arc::new(SolidColor::new(Color::new(1.0, 0.5, 1.0))));
+        let expected = Vec3::new(1.0, 0.0, 1.0).unit_vector();
+        assert!((tilted.perturb(&flat_hit(true)).normal - expected).length() < 1e-12);
+        assert!((tilted.perturb(&flat_hit(false)).normal + expected).length() < 1e-12);
+    }
+
+    struct Ramp;
+
+    impl crate::texture::Texture for Ramp {
+        fn value(&self, u: f64, _v: f64, _p: &Point) -> Color {
+            Color::new(u, u, u)
+        }
+    }
+
+    #[test]
+    fn test_bump_map() {
+        let inner: Mat = Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)));
+
+        // the height rises by 0.5 over the 2 units of u
+        let bump = BumpMap::new(inner, Arc::new(Ramp), 1.0);
+        let n = bump.perturb(&flat_hit(true)).normal;
+        let expected = Vec3::new(-0.5, 0.0, 1.0).unit_vector();
+        assert!((n - expected).length() < 1e-9, "{:?}", n);
+    }
+}
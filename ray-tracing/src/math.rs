@@ -0,0 +1,10 @@
+//! The vector and matrix types, together with the functions operating on
+//! them, for use in custom [`Hittable`](crate::hittable::Hittable) and
+//! [`Material`](crate::material::Material) implementations.
+
+pub use crate::{
+    cvec::{cross, dot, reflect, refract, CVec},
+    ray::{Point, Vec3},
+    render::Color,
+    transform::{Mat4, Quat, Transform},
+};
@@ -0,0 +1,467 @@
+//! Rough surfaces modelled as a distribution of perfect mirror microfacets
+//! with the GGX (Trowbridge-Reitz) distribution.
+//!
+//! The facets are sampled from the distribution of the normals visible from
+//! the incoming direction, which leaves the masking term of the outgoing
+//! direction as the weight of a sample. Light scattered more than once
+//! between the facets is lost, so rough surfaces get darker.
+
+use std::f64::consts::PI;
+
+use rand::RngCore;
+
+use crate::{
+    cvec::{dot, reflect, refract},
+    hittable::HitRecord,
+    material::{tangent_frame, Material},
+    ray::{Ray, Vec3},
+    render::Color,
+    rtweekend,
+};
+
+/// The smallest alpha, smoother surfaces make the distribution degenerate.
+const MIN_ALPHA: f64 = 1e-4;
+
+/// A GGX distribution of microfacet normals around `+Z`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ggx {
+    pub alpha: f64,
+}
+
+impl Ggx {
+    /// Maps the perceptually linear roughness in `0..=1` to alpha.
+    pub fn from_roughness(roughness: f64) -> Self {
+        Self {
+            alpha: (roughness * roughness).max(MIN_ALPHA),
+        }
+    }
+
+    /// Smith's auxiliary function for the direction `w`.
+    fn lambda(&self, w: Vec3) -> f64 {
+        let cos2 = w.z() * w.z();
+        if cos2 == 0.0 {
+            return f64::INFINITY;
+        }
+
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        0.5 * ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0)
+    }
+
+    /// The fraction of the facets visible from `w`.
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// The fraction of the facets visible from both directions, with the
+    /// height correlated masking and shadowing.
+    pub fn g2(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// The density of the facet normals projected onto the macro surface.
+    pub fn d(&self, m: Vec3) -> f64 {
+        if m.z() <= 0.0 {
+            return 0.0;
+        }
+
+        let a2 = self.alpha * self.alpha;
+        let cos2 = m.z() * m.z();
+        let t = cos2 * (a2 - 1.0) + 1.0;
+        a2 / (PI * t * t)
+    }
+
+    /// Samples a facet normal visible from `wo`, which has to be above the
+    /// surface, after Eric Heitz, "Sampling the GGX Distribution of Visible
+    /// Normals", 2018.
+    pub fn sample_visible_normal(&self, wo: Vec3, u1: f64, u2: f64) -> Vec3 {
+        // stretch to the hemisphere configuration
+        let vh = Vec3::new(self.alpha * wo.x(), self.alpha * wo.y(), wo.z()).unit_vector();
+
+        let len2 = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len2 > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / len2.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(&t1);
+
+        // a point on the disk, squeezed onto the visible half
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        // and back to the ellipsoid
+        Vec3::new(self.alpha * nh.x(), self.alpha * nh.y(), nh.z().max(0.0)).unit_vector()
+    }
+}
+
+/// The Fresnel reflectance of a conductor, for the complex index of
+/// refraction `eta + ik` relative to the outside.
+pub fn fresnel_conductor(cos_i: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_i * cos_i;
+    let sin2 = 1.0 - cos2;
+    let (eta2, k2) = (eta * eta, k * k);
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * cos_i * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rp + rs)
+}
+
+/// The Fresnel reflectance of a dielectric, `etai_over_etat` is the ratio
+/// of the indices on the incident and the transmitted side. One on total
+/// internal reflection.
+pub fn fresnel_dielectric(cos_i: f64, etai_over_etat: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = etai_over_etat * etai_over_etat * (1.0 - cos_i * cos_i);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let eta = etai_over_etat;
+    let rs = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let rp = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+
+    0.5 * (rs * rs + rp * rp)
+}
+
+/// A complex index of refraction per color channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComplexIor {
+    pub eta: Color,
+    pub k: Color,
+}
+
+impl ComplexIor {
+    pub fn new(eta: Color, k: Color) -> Self {
+        Self { eta, k }
+    }
+
+    pub fn gold() -> Self {
+        Self::new(
+            Color::new(0.143, 0.374, 1.442),
+            Color::new(3.983, 2.385, 1.603),
+        )
+    }
+
+    pub fn copper() -> Self {
+        Self::new(
+            Color::new(0.200, 0.924, 1.102),
+            Color::new(3.912, 2.452, 2.142),
+        )
+    }
+
+    pub fn aluminum() -> Self {
+        Self::new(
+            Color::new(1.657, 0.880, 0.521),
+            Color::new(9.224, 6.270, 4.837),
+        )
+    }
+
+    pub fn fresnel(&self, cos_i: f64) -> Color {
+        Color::new(
+            fresnel_conductor(cos_i, self.eta.x(), self.k.x()),
+            fresnel_conductor(cos_i, self.eta.y(), self.k.y()),
+            fresnel_conductor(cos_i, self.eta.z(), self.k.z()),
+        )
+    }
+}
+
+/// Moves directions between the world and the frame of a hit, with the
+/// normal as `+Z`.
+struct Frame {
+    t: Vec3,
+    b: Vec3,
+    n: Vec3,
+}
+
+impl Frame {
+    fn new(rec: &HitRecord) -> Self {
+        let (t, b) = tangent_frame(rec.normal, rec.tangent, rec.bitangent);
+        Self {
+            t,
+            b,
+            n: rec.normal,
+        }
+    }
+
+    fn to_local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(dot(v, self.t), dot(v, self.b), dot(v, self.n))
+    }
+
+    fn to_world(&self, v: Vec3) -> Vec3 {
+        v.x() * self.t + v.y() * self.b + v.z() * self.n
+    }
+}
+
+/// A rough metal with the GGX microfacet distribution.
+pub struct Conductor {
+    pub ior: ComplexIor,
+    pub distribution: Ggx,
+}
+
+impl Conductor {
+    pub fn new(ior: ComplexIor, roughness: f64) -> Self {
+        Self {
+            ior,
+            distribution: Ggx::from_roughness(roughness),
+        }
+    }
+
+    pub fn gold(roughness: f64) -> Self {
+        Self::new(ComplexIor::gold(), roughness)
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Self::new(ComplexIor::copper(), roughness)
+    }
+
+    pub fn aluminum(roughness: f64) -> Self {
+        Self::new(ComplexIor::aluminum(), roughness)
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        let frame = Frame::new(rec);
+        let wo = frame.to_local(-r_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return false;
+        }
+
+        let m = self.distribution.sample_visible_normal(
+            wo,
+            rtweekend::rand_range(rng, 0.0..1.0),
+            rtweekend::rand_range(rng, 0.0..1.0),
+        );
+        let wi = reflect(-wo, m);
+        if wi.z() <= 0.0 {
+            return false;
+        }
+
+        let g = self.distribution.g2(wo, wi) / self.distribution.g1(wo);
+        *attenuation = self.ior.fresnel(dot(wo, m)) * g;
+        *scattered = Ray::new(rec.p, frame.to_world(wi));
+
+        true
+    }
+}
+
+/// Rough glass with the GGX microfacet distribution.
+pub struct RoughDielectric {
+    pub ir: f64,
+    pub distribution: Ggx,
+}
+
+impl RoughDielectric {
+    pub fn new(ir: f64, roughness: f64) -> Self {
+        Self {
+            ir,
+            distribution: Ggx::from_roughness(roughness),
+        }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        let refraction_ratio = if rec.front_face {
+            1.0 / self.ir
+        } else {
+            self.ir
+        };
+
+        // the normal is always on the side of the ray
+        let frame = Frame::new(rec);
+        let wo = frame.to_local(-r_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return false;
+        }
+
+        let m = self.distribution.sample_visible_normal(
+            wo,
+            rtweekend::rand_range(rng, 0.0..1.0),
+            rtweekend::rand_range(rng, 0.0..1.0),
+        );
+
+        // choosing by the Fresnel term cancels it from the weight
+        let fresnel = fresnel_dielectric(dot(wo, m), refraction_ratio);
+        let wi = if rtweekend::rand_range(rng, 0.0..1.0) < fresnel {
+            let wi = reflect(-wo, m);
+            if wi.z() <= 0.0 {
+                return false;
+            }
+            wi
+        } else {
+            let wi = refract(-wo, m, refraction_ratio);
+            if wi.z() >= 0.0 {
+                return false;
+            }
+            wi
+        };
+
+        let g = self.distribution.g2(wo, wi) / self.distribution.g1(wo);
+        *attenuation = Color::new(g, g, g);
+        *scattered = Ray::new(rec.p, frame.to_world(wi));
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Point;
+
+    fn assert_close(l: f64, r: f64, eps: f64) {
+        assert!((l - r).abs() < eps, "{} != {}", l, r);
+    }
+
+    fn hit(front_face: bool) -> HitRecord {
+        HitRecord {
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            front_face,
+            ..HitRecord::default()
+        }
+    }
+
+    #[test]
+    fn test_fresnel() {
+        // the closed form at normal incidence
+        let (eta, k) = (0.2, 3.9);
+        let expected = ((eta - 1.0) * (eta - 1.0) + k * k) / ((eta + 1.0) * (eta + 1.0) + k * k);
+        assert_close(fresnel_conductor(1.0, eta, k), expected, 1e-12);
+        assert_close(fresnel_conductor(0.0, eta, k), 1.0, 1e-12);
+
+        assert_close(fresnel_dielectric(1.0, 1.0 / 1.5), 0.04, 1e-12);
+        assert_close(fresnel_dielectric(0.0, 1.0 / 1.5), 1.0, 1e-12);
+        assert_eq!(fresnel_dielectric(0.5, 1.5), 1.0);
+
+        // gold is more reflective in red than in blue
+        let gold = ComplexIor::gold().fresnel(1.0);
+        assert!(gold.x() > 0.9 && gold.z() < 0.5, "{:?}", gold);
+    }
+
+    #[test]
+    fn test_ggx() {
+        let ggx = Ggx::from_roughness(0.7);
+        let mut rng = rtweekend::stream_rng(0, 0);
+
+        // the distribution projected onto the surface integrates to one
+        let n = 200_000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let m = Vec3::random_unit_vector(&mut rng);
+            let m = Vec3::new(m.x(), m.y(), m.z().abs());
+            sum += ggx.d(m) * m.z() * 2.0 * PI;
+        }
+        assert_close(sum / n as f64, 1.0, 0.02);
+
+        let wo = Vec3::new(0.6, 0.0, 0.8);
+        for _ in 0..1000 {
+            let m = ggx.sample_visible_normal(
+                wo,
+                rtweekend::rand_range(&mut rng, 0.0..1.0),
+                rtweekend::rand_range(&mut rng, 0.0..1.0),
+            );
+            assert_close(m.length(), 1.0, 1e-9);
+            assert!(m.z() >= 0.0 && dot(wo, m) >= 0.0);
+        }
+    }
+
+    #[test]
+    fn test_conductor() {
+        let r = Ray::new(Point::new(0.0, 1.0, 1.0), Vec3::new(0.0, -1.0, -1.0));
+        let mut attenuation = Color::default();
+        let mut scattered = Ray::new(Point::default(), Vec3::default());
+        let mut rng = rtweekend::stream_rng(0, 0);
+
+        // smooth, it is a mirror
+        let smooth = Conductor::gold(0.0);
+        assert!(smooth.scatter(&r, &hit(true), &mut attenuation, &mut scattered, &mut rng));
+        let dir = scattered.direction().unit_vector();
+        assert!((dir - Vec3::new(0.0, 1.0, -1.0).unit_vector()).length() < 1e-3);
+
+        // rough, a perfect reflector doesn't gain any energy and only loses
+        // the light scattered more than once
+        let mirror = Conductor::new(
+            ComplexIor::new(Color::new(0.0, 0.0, 0.0), Color::new(1e6, 1e6, 1e6)),
+            0.5,
+        );
+        let n = 10_000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            if mirror.scatter(&r, &hit(true), &mut attenuation, &mut scattered, &mut rng) {
+                assert!(dot(scattered.direction(), hit(true).normal) > 0.0);
+                assert!(attenuation.x() <= 1.0);
+                sum += attenuation.x();
+            }
+        }
+        let albedo = sum / n as f64;
+        assert!(albedo > 0.85 && albedo < 0.95, "{}", albedo);
+    }
+
+    #[test]
+    fn test_rough_dielectric() {
+        let r = Ray::new(Point::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut attenuation = Color::default();
+        let mut scattered = Ray::new(Point::default(), Vec3::default());
+        let mut rng = rtweekend::stream_rng(0, 0);
+
+        let glass = RoughDielectric::new(1.5, 0.3);
+        let (mut reflected, mut refracted) = (0, 0);
+        for _ in 0..1000 {
+            if glass.scatter(&r, &hit(true), &mut attenuation, &mut scattered, &mut rng) {
+                assert!(attenuation.x() > 0.0 && attenuation.x() <= 1.0);
+                if scattered.direction().y() > 0.0 {
+                    reflected += 1;
+                } else {
+                    refracted += 1;
+                }
+            }
+        }
+        // about four percent are reflected at normal incidence
+        assert!(reflected > 10 && reflected < 100, "{}", reflected);
+        assert!(refracted > 900, "{}", refracted);
+
+        // smooth, straight through from either side
+        let smooth = RoughDielectric::new(1.5, 0.0);
+        for rec in [hit(true), hit(false)] {
+            loop {
+                assert!(smooth.scatter(&r, &rec, &mut attenuation, &mut scattered, &mut rng));
+                if scattered.direction().y() < 0.0 {
+                    break;
+                }
+            }
+            let dir = scattered.direction().unit_vector();
+            assert!((dir - Vec3::new(0.0, -1.0, 0.0)).length() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Seedable gradient and cellular noise for procedural textures.
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    cvec::dot,
+    ray::{Point, Vec3},
+    rtweekend::{self, stream_rng, NOISE_STREAM},
+};
+
+const POINT_COUNT: usize = 256;
+
+/// Random permutations of the lattice coordinates, hashing a cell to one of
+/// the `POINT_COUNT` random values.
+struct Permutation {
+    x: Vec<usize>,
+    y: Vec<usize>,
+    z: Vec<usize>,
+}
+
+impl Permutation {
+    fn new<G: Rng + ?Sized>(rng: &mut G) -> Self {
+        let mut permute = || {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            p.shuffle(rng);
+            p
+        };
+
+        Self {
+            x: permute(),
+            y: permute(),
+            z: permute(),
+        }
+    }
+
+    fn hash(&self, i: i64, j: i64, k: i64) -> usize {
+        // the mask keeps negative cells in range as well
+        let m = POINT_COUNT as i64 - 1;
+        self.x[(i & m) as usize] ^ self.y[(j & m) as usize] ^ self.z[(k & m) as usize]
+    }
+}
+
+fn cell(p: &Point) -> (i64, i64, i64) {
+    (
+        p.x().floor() as i64,
+        p.y().floor() as i64,
+        p.z().floor() as i64,
+    )
+}
+
+/// Gradient noise after Ken Perlin, with random unit gradients on the
+/// integer lattice.
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm: Permutation,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        Self::with_rng(&mut stream_rng(seed, NOISE_STREAM))
+    }
+
+    pub fn with_rng<G: Rng + ?Sized>(rng: &mut G) -> Self {
+        let gradients = (0..POINT_COUNT)
+            .map(|_| Vec3::random_unit_vector(rng))
+            .collect();
+
+        Self {
+            gradients,
+            perm: Permutation::new(rng),
+        }
+    }
+
+    /// Smooth noise in about `-1..1`, zero on the lattice points.
+    pub fn noise(&self, p: &Point) -> f64 {
+        let (i, j, k) = cell(p);
+        let u = p.x() - p.x().floor();
+        let v = p.y() - p.y().floor();
+        let w = p.z() - p.z().floor();
+
+        // Hermite cubic to round off the lattice
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = self.gradients[self.perm.hash(i + di, j + dj, k + dk)];
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+                    let weight = Vec3::new(u - fi, v - fj, w - fk);
+
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * dot(gradient, weight);
+                }
+            }
+        }
+
+        accum
+    }
+
+    /// Sum of `depth` octaves of noise, each at double the frequency and
+    /// half the weight of the last one.
+    pub fn turb(&self, p: &Point, depth: usize) -> f64 {
+        let mut accum = 0.0;
+        let mut temp = *p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(&temp);
+            weight *= 0.5;
+            temp *= 2.0;
+        }
+
+        accum.abs()
+    }
+}
+
+/// Cellular noise after Steven Worley, with one random feature point in
+/// every unit cell.
+pub struct Worley {
+    points: Vec<Vec3>,
+    perm: Permutation,
+}
+
+impl Worley {
+    pub fn new(seed: u64) -> Self {
+        Self::with_rng(&mut stream_rng(seed, NOISE_STREAM))
+    }
+
+    pub fn with_rng<G: Rng + ?Sized>(rng: &mut G) -> Self {
+        let points = (0..POINT_COUNT)
+            .map(|_| {
+                Vec3::new(
+                    rtweekend::rand_range(rng, 0.0..1.0),
+                    rtweekend::rand_range(rng, 0.0..1.0),
+                    rtweekend::rand_range(rng, 0.0..1.0),
+                )
+            })
+            .collect();
+
+        Self {
+            points,
+            perm: Permutation::new(rng),
+        }
+    }
+
+    /// The distances to the nearest and the second nearest feature point.
+    pub fn distances(&self, p: &Point) -> (f64, f64) {
+        let (i, j, k) = cell(p);
+        let mut f1 = f64::INFINITY;
+        let mut f2 = f64::INFINITY;
+
+        // only the neighbouring cells, points further away are rarely closer
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let (ci, cj, ck) = (i + di, j + dj, k + dk);
+                    let corner = Point::new(ci as f64, cj as f64, ck as f64);
+                    let feature = corner + self.points[self.perm.hash(ci, cj, ck)];
+                    let d = (feature - *p).length_squared();
+
+                    if d < f1 {
+                        f2 = f1;
+                        f1 = d;
+                    } else if d < f2 {
+                        f2 = d;
+                    }
+                }
+            }
+        }
+
+        (f1.sqrt(), f2.sqrt())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples() -> impl Iterator<Item = Point> {
+        (0..200).map(|i| {
+            let t = i as f64 * 0.173;
+            Point::new(t * 1.3 - 17.0, (t * 0.7).sin() * 5.0, t * 0.31 - 3.0)
+        })
+    }
+
+    #[test]
+    fn test_perlin() {
+        let a = Perlin::new(1);
+        let b = Perlin::new(1);
+        let c = Perlin::new(2);
+
+        assert_eq!(a.noise(&Point::new(3.0, -2.0, 7.0)), 0.0);
+
+        let mut differs = false;
+        for p in samples() {
+            let n = a.noise(&p);
+            assert!(n.abs() <= 1.0, "{} at {:?}", n, p);
+            assert_eq!(n, b.noise(&p));
+            assert!(a.turb(&p, 7) >= 0.0);
+            differs |= n != c.noise(&p);
+        }
+        assert!(differs, "the seed doesn't change the noise");
+    }
+
+    #[test]
+    fn test_worley() {
+        let a = Worley::new(1);
+        let b = Worley::new(1);
+
+        for p in samples() {
+            let (f1, f2) = a.distances(&p);
+            assert!(f1 <= f2);
+            assert!(f1 <= 3f64.sqrt());
+            assert_eq!((f1, f2), b.distances(&p));
+        }
+    }
+}
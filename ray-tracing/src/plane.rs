@@ -0,0 +1,101 @@
+use crate::{
+    aabb::Aabb,
+    cvec::{cross, dot},
+    hittable::{HitRecord, Hittable},
+    material::Mat,
+    ray::{Point, Ray, Vec3},
+};
+
+/// An infinite plane.
+///
+/// As it has no bounding box it can't be put into a bvh, add it next to it
+/// in a [`HittableList`](crate::hittable::HittableList) instead.
+pub struct Plane {
+    pub point: Point,
+    pub normal: Vec3,
+    pub mat: Mat,
+    tangent: Vec3,
+    bitangent: Vec3,
+}
+
+impl Plane {
+    pub fn new(point: Point, normal: Vec3, mat: Mat) -> Self {
+        let normal = normal.unit_vector();
+
+        let helper = if normal.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let tangent = cross(&helper, &normal).unit_vector();
+        let bitangent = cross(&normal, &tangent);
+
+        Self {
+            point,
+            normal,
+            mat,
+            tangent,
+            bitangent,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let denom = dot(self.normal, r.direction());
+        if denom.abs() < 1e-12 {
+            return false;
+        }
+
+        let t = dot(self.point - r.origin(), self.normal) / denom;
+        if t < t_min || t_max < t {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = r.at(t);
+
+        // the texture repeats every unit along the plane
+        let d = rec.p - self.point;
+        rec.u = dot(d, self.tangent).rem_euclid(1.0);
+        rec.v = dot(d, self.bitangent).rem_euclid(1.0);
+        rec.tangent = self.tangent;
+        rec.bitangent = self.bitangent;
+
+        rec.set_face_normal(r, &self.normal);
+        rec.mat = Some(self.mat.clone());
+
+        true
+    }
+
+    fn bounding_box(&self, _output_box: &mut Aabb) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{material::Lambartian, render::Color};
+
+    #[test]
+    fn test_hit() {
+        let mat = Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)));
+        let plane = Plane::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), mat);
+        let mut rec = HitRecord::default();
+
+        let r = Ray::new(Point::new(100.25, 3.0, -7.5), Vec3::new(0.0, -1.0, 0.0));
+        assert!(plane.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert_eq!(rec.t, 3.0);
+        assert_eq!(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert!(rec.front_face);
+        assert!((0.0..1.0).contains(&rec.u) && (0.0..1.0).contains(&rec.v));
+
+        let parallel = Ray::new(Point::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(!plane.hit(&parallel, 0.001, f64::INFINITY, &mut rec));
+
+        assert!(!plane.bounding_box(&mut Aabb::default()));
+    }
+}
@@ -0,0 +1,122 @@
+//! Error metrics between two images, e.g. a render and its reference.
+
+use crate::render::ImageBuffer;
+
+/// How far an image is off its reference, over all channels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageDiff {
+    /// Root mean square error.
+    pub rmse: f64,
+    /// Peak signal to noise ratio in dB, with a peak of one.
+    pub psnr: f64,
+    /// Largest absolute difference of a single channel.
+    pub max_error: f64,
+}
+
+/// Limits for a comparison to pass.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    pub max_rmse: f64,
+    pub min_psnr: f64,
+    pub max_error: f64,
+}
+
+impl Tolerance {
+    /// Only identical images pass.
+    pub const EXACT: Self = Self {
+        max_rmse: 0.0,
+        min_psnr: f64::INFINITY,
+        max_error: 0.0,
+    };
+}
+
+impl ImageDiff {
+    pub fn within(&self, tolerance: &Tolerance) -> bool {
+        self.rmse <= tolerance.max_rmse
+            && self.psnr >= tolerance.min_psnr
+            && self.max_error <= tolerance.max_error
+    }
+}
+
+/// Compares the images channel by channel, `None` if the sizes differ.
+pub fn compare(image: &ImageBuffer, reference: &ImageBuffer) -> Option<ImageDiff> {
+    if image.get_width() != reference.get_width() || image.get_height() != reference.get_height() {
+        return None;
+    }
+
+    let mut sum = 0.0;
+    let mut max_error: f64 = 0.0;
+    let mut count = 0;
+
+    for (a, b) in image.get_pixels().iter().zip(reference.get_pixels()) {
+        for &d in (*a - *b).data() {
+            sum += d * d;
+            max_error = max_error.max(d.abs());
+            count += 1;
+        }
+    }
+
+    let mse = if count == 0 { 0.0 } else { sum / count as f64 };
+
+    Some(ImageDiff {
+        rmse: mse.sqrt(),
+        psnr: -10.0 * mse.log10(),
+        max_error,
+    })
+}
+
+/// The absolute difference of every pixel.
+///
+/// # Panics
+/// If the sizes differ.
+pub fn diff_image(image: &ImageBuffer, reference: &ImageBuffer) -> ImageBuffer {
+    assert!(
+        image.get_width() == reference.get_width() && image.get_height() == reference.get_height(),
+        "the images have different sizes"
+    );
+
+    let pixels = image
+        .get_pixels()
+        .iter()
+        .zip(reference.get_pixels())
+        .map(|(a, b)| (*a - *b).abs())
+        .collect();
+
+    ImageBuffer::from_pixels(pixels, image.get_height(), image.get_width())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Color;
+
+    #[test]
+    fn test_compare() {
+        let a = ImageBuffer::from_pixels(
+            vec![Color::new(0.5, 0.5, 0.5), Color::new(0.0, 0.0, 0.0)],
+            1,
+            2,
+        );
+        let mut b = a.clone();
+
+        let same = compare(&a, &b).unwrap();
+        assert_eq!(same.rmse, 0.0);
+        assert_eq!(same.psnr, f64::INFINITY);
+        assert!(same.within(&Tolerance::EXACT));
+
+        b.set_pixel(1, 0, Color::new(0.6, 0.0, 0.0));
+        let diff = compare(&a, &b).unwrap();
+        // one of six channels is off by 0.6
+        assert!((diff.rmse - 0.06f64.sqrt()).abs() < 1e-12);
+        assert!((diff.psnr - 10.0 * (1.0 / 0.06f64).log10()).abs() < 1e-9);
+        assert!((diff.max_error - 0.6).abs() < 1e-12);
+        assert!(!diff.within(&Tolerance::EXACT));
+
+        assert_eq!(
+            diff_image(&a, &b).get_pixels(),
+            &[Color::new(0.0, 0.0, 0.0), Color::new(0.6, 0.0, 0.0)]
+        );
+
+        assert!(compare(&a, &ImageBuffer::new(2, 1)).is_none());
+    }
+}
@@ -0,0 +1,194 @@
+//! OpenEXR output, storing the linear values as uncompressed half floats.
+
+use crate::render::Render;
+use std::{fs, io, path::Path};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+/// Version 2, single part scan line file.
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+const HALF: i32 = 1;
+
+/// The channels have to be sorted by name.
+const CHANNELS: [&str; 3] = ["B", "G", "R"];
+
+/// Saves the linear pixel values as a scan line OpenEXR image.
+pub fn save<'a, T: Render<'a>, P: AsRef<Path>>(image: T, path: P) -> Result<(), io::Error> {
+    let img = image.image();
+    let (width, height) = (img.get_width(), img.get_height());
+
+    let mut data = MAGIC.to_vec();
+    data.extend_from_slice(&VERSION);
+
+    let mut channels = Vec::new();
+    for name in CHANNELS {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&HALF.to_le_bytes());
+        // linear flag, reserved bytes, x and y sampling
+        channels.extend_from_slice(&[0; 4]);
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+
+    let mut window = Vec::new();
+    for v in [0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&v.to_le_bytes());
+    }
+
+    write_attribute(&mut data, "channels", "chlist", &channels);
+    write_attribute(&mut data, "compression", "compression", &[0]);
+    write_attribute(&mut data, "dataWindow", "box2i", &window);
+    write_attribute(&mut data, "displayWindow", "box2i", &window);
+    write_attribute(&mut data, "lineOrder", "lineOrder", &[0]);
+    write_attribute(&mut data, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    write_attribute(&mut data, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(&mut data, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    data.push(0);
+
+    // one offset per scan line, pointing at its block
+    let line_size = width * CHANNELS.len() * 2;
+    let block_size = 8 + line_size;
+    let table_end = data.len() + height * 8;
+    for y in 0..height {
+        data.extend_from_slice(&((table_end + y * block_size) as u64).to_le_bytes());
+    }
+
+    for (y, row) in img.get_pixels().chunks_exact(width.max(1)).enumerate() {
+        data.extend_from_slice(&(y as i32).to_le_bytes());
+        data.extend_from_slice(&(line_size as i32).to_le_bytes());
+
+        // the values are grouped by channel, in the order of the channel list
+        for c in [2, 1, 0] {
+            for p in row {
+                data.extend_from_slice(&f32_to_f16(p[c] as f32).to_le_bytes());
+            }
+        }
+    }
+
+    let path = path.as_ref().to_string_lossy();
+
+    fs::write(
+        if path.ends_with(".exr") {
+            path.to_string()
+        } else {
+            format!("{}.exr", path)
+        },
+        data,
+    )
+}
+
+fn write_attribute(out: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    out.extend_from_slice(name.as_bytes());
+    out.push(0);
+    out.extend_from_slice(kind.as_bytes());
+    out.push(0);
+    out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+/// Converts to a half float, rounding to the nearest even value.
+fn f32_to_f16(v: f32) -> u16 {
+    let bits = v.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mant = bits & 0x7f_ffff;
+
+    let round = |half: u32, rem: u32, halfway: u32| {
+        if rem > halfway || (rem == halfway && half & 1 == 1) {
+            half + 1
+        } else {
+            half
+        }
+    };
+
+    if exp == 0xff {
+        // keep NaNs as NaNs
+        return sign | 0x7c00 | if mant != 0 { 0x200 } else { 0 };
+    }
+
+    let e = exp - 127 + 15;
+    if e >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    if e <= 0 {
+        if e < -10 {
+            return sign;
+        }
+
+        // subnormal, the implicit leading bit becomes part of the mantissa
+        let m = mant | 0x80_0000;
+        let shift = (14 - e) as u32;
+        let half = round(m >> shift, m & ((1 << shift) - 1), 1 << (shift - 1));
+        return sign | half as u16;
+    }
+
+    // a carry out of the mantissa correctly bumps the exponent
+    let half = round(((e as u32) << 10) | (mant >> 13), mant & 0x1fff, 0x1000);
+    sign | half as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::TryInto, fs, io};
+
+    use super::*;
+    use crate::render::{Color, Image};
+
+    #[test]
+    fn test_f16() {
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(0.1), 0x2e66);
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16(1e6), 0x7c00);
+        assert_eq!(f32_to_f16(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_f16(2f32.powi(-14)), 0x0400);
+        assert_eq!(f32_to_f16(2f32.powi(-26)), 0x0000);
+        assert!(f32_to_f16(f32::NAN) & 0x3ff != 0);
+    }
+
+    #[test]
+    fn test_save() -> Result<(), io::Error> {
+        let px = [
+            Color::new(1.0, 0.5, 0.0),
+            Color::new(2.0, 0.0, 1.0),
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(0.0, 0.0, 0.0),
+        ];
+        let img = Image::new(&px, 3, 2);
+
+        let tmp = tempfile::Builder::new().suffix(".exr").tempfile()?;
+        save(img, tmp.path())?;
+        let data = fs::read(tmp.path())?;
+
+        assert_eq!(&data[..4], &MAGIC);
+        assert_eq!(&data[4..8], &VERSION);
+
+        // the first offset points right behind the offset table
+        let last = b"screenWindowWidth\0float\0";
+        let pos = data.windows(last.len()).position(|w| w == last).unwrap();
+        let table = pos + last.len() + 4 + 4 + 1;
+        let first = u64::from_le_bytes(data[table..table + 8].try_into().unwrap()) as usize;
+        assert_eq!(first, table + 3 * 8);
+
+        let line_size = 2 * 3 * 2;
+        assert_eq!(data.len(), first + 3 * (8 + line_size));
+
+        let block = &data[first..first + 8 + line_size];
+        assert_eq!(&block[..8], &[0, 0, 0, 0, 12, 0, 0, 0]);
+        let halves: Vec<u16> = block[8..]
+            .chunks_exact(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        // blue, green then red values of both pixels
+        assert_eq!(halves, [0x0000, 0x3c00, 0x3800, 0x0000, 0x3c00, 0x4000]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+//! Radiance `.hdr` output, storing the linear values as run length encoded
+//! RGBE pixels.
+
+use crate::render::{Color, Render};
+use std::{fs, io, path::Path};
+
+/// Widths outside of this range have to be stored without run length
+/// encoding.
+const RLE_WIDTH: std::ops::Range<usize> = 8..0x8000;
+const MAX_RUN: usize = 127;
+const MAX_LITERAL: usize = 128;
+
+/// Saves the linear pixel values as a Radiance RGBE image.
+pub fn save<'a, T: Render<'a>, P: AsRef<Path>>(image: T, path: P) -> Result<(), io::Error> {
+    let img = image.image();
+    let (width, height) = (img.get_width(), img.get_height());
+
+    let mut data = format!(
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )
+    .into_bytes();
+
+    for row in img.get_pixels().chunks_exact(width.max(1)) {
+        let pixels: Vec<_> = row.iter().copied().map(rgbe).collect();
+
+        if !RLE_WIDTH.contains(&width) {
+            data.extend(pixels.iter().flatten());
+            continue;
+        }
+
+        data.extend_from_slice(&[2, 2, (width >> 8) as u8, width as u8]);
+
+        // every component is encoded on its own
+        let mut component = Vec::with_capacity(width);
+        for i in 0..4 {
+            component.clear();
+            component.extend(pixels.iter().map(|p| p[i]));
+            write_rle(&mut data, &component);
+        }
+    }
+
+    let path = path.as_ref().to_string_lossy();
+
+    fs::write(
+        if path.ends_with(".hdr") {
+            path.to_string()
+        } else {
+            format!("{}.hdr", path)
+        },
+        data,
+    )
+}
+
+/// Converts the color into a shared exponent and three mantissas, negative
+/// values and NaN are stored as zero, values above the largest RGBE value
+/// as that.
+fn rgbe(c: Color) -> [u8; 4] {
+    // NaN becomes zero and infinity the largest float
+    let channel = |v: f64| {
+        if v.is_nan() {
+            0.0
+        } else {
+            v.clamp(0.0, f64::MAX)
+        }
+    };
+    let (r, g, b) = (channel(c.x()), channel(c.y()), channel(c.z()));
+    let m = r.max(g).max(b);
+
+    if !m.is_finite() || m < 1e-32 {
+        return [0; 4];
+    }
+
+    // m = f * 2^e with f in 0.5..1, the exponent is stored with a bias of 128
+    let mut e = (m.log2().floor() as i32 + 1).clamp(-128, 127);
+    let mut f = m / 2f64.powi(e);
+    if f >= 1.0 && e < 127 {
+        f /= 2.0;
+        e += 1;
+    }
+
+    let scale = f.min(255.0 / 256.0) * 256.0 / m;
+    [
+        (r * scale) as u8,
+        (g * scale) as u8,
+        (b * scale) as u8,
+        (e + 128) as u8,
+    ]
+}
+
+/// Length of the run of equal bytes at the start of the data.
+fn run_length(data: &[u8]) -> usize {
+    data.iter()
+        .take(MAX_RUN)
+        .take_while(|&&v| v == data[0])
+        .count()
+}
+
+/// Writes the data as runs of at least four equal bytes and literal blocks.
+fn write_rle(out: &mut Vec<u8>, data: &[u8]) {
+    let mut i = 0;
+
+    while i < data.len() {
+        // search the next run worth encoding
+        let mut run_start = i;
+        let mut run_len = 0;
+        while run_start < data.len() {
+            run_len = run_length(&data[run_start..]);
+            if run_len >= 4 {
+                break;
+            }
+            run_start += run_len;
+        }
+
+        while i < run_start {
+            let n = (run_start - i).min(MAX_LITERAL);
+            out.push(n as u8);
+            out.extend_from_slice(&data[i..i + n]);
+            i += n;
+        }
+
+        if run_start < data.len() {
+            out.push((128 + run_len) as u8);
+            out.push(data[run_start]);
+            i = run_start + run_len;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io};
+
+    use super::*;
+    use crate::render::Image;
+
+    fn decode_rle(data: &[u8], len: usize) -> (Vec<u8>, usize) {
+        let mut out = Vec::new();
+        let mut i = 0;
+        while out.len() < len {
+            let n = data[i] as usize;
+            if n > 128 {
+                out.resize(out.len() + n - 128, data[i + 1]);
+                i += 2;
+            } else {
+                out.extend_from_slice(&data[i + 1..i + 1 + n]);
+                i += 1 + n;
+            }
+        }
+        (out, i)
+    }
+
+    #[test]
+    fn test_rgbe() {
+        assert_eq!(rgbe(Color::new(1.0, 0.5, 0.0)), [128, 64, 0, 129]);
+        assert_eq!(rgbe(Color::new(0.0, 3.0, -1.0)), [0, 192, 0, 130]);
+        assert_eq!(rgbe(Color::new(0.0, 0.0, 0.0)), [0; 4]);
+
+        // clamped to the largest value
+        assert_eq!(
+            rgbe(Color::new(f64::INFINITY, 1.0, f64::NAN)),
+            [255, 0, 0, 255]
+        );
+        assert_eq!(rgbe(Color::new(1e300, 0.0, 0.0)), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_rle() {
+        let mut data: Vec<u8> = (0..200).map(|i| (i * 7) as u8).collect();
+        data.extend([5; 300]);
+        data.extend([1, 2, 2, 2, 3]);
+
+        let mut out = Vec::new();
+        write_rle(&mut out, &data);
+        assert!(out.len() < data.len());
+
+        let (decoded, used) = decode_rle(&out, data.len());
+        assert_eq!(decoded, data);
+        assert_eq!(used, out.len());
+    }
+
+    #[test]
+    fn test_save() -> Result<(), io::Error> {
+        let px = vec![Color::new(1.0, 0.5, 0.0); 10 * 2];
+        let img = Image::new(&px, 2, 10);
+
+        let tmp = tempfile::Builder::new().suffix(".hdr").tempfile()?;
+        save(img, tmp.path())?;
+        let data = fs::read(tmp.path())?;
+
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 10\n";
+        assert_eq!(&data[..header.len()], header);
+
+        let mut rest = &data[header.len()..];
+        for _ in 0..2 {
+            assert_eq!(&rest[..4], &[2, 2, 0, 10]);
+            rest = &rest[4..];
+
+            for expected in [128, 64, 0, 129] {
+                let (decoded, used) = decode_rle(rest, 10);
+                assert_eq!(decoded, vec![expected; 10]);
+                rest = &rest[used..];
+            }
+        }
+        assert!(rest.is_empty());
+
+        Ok(())
+    }
+}
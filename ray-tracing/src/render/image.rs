@@ -1,7 +1,12 @@
 use crate::cvec;
+use std::{
+    error, fmt, io,
+    ops::{Index, IndexMut},
+};
 
 pub type Color = cvec::Color<f64>;
 
+#[derive(Debug, Clone, Copy)]
 pub struct Image<'a> {
     pixels: &'a [Color],
     height: usize,
@@ -32,11 +37,381 @@ impl<'a> Image<'a> {
 }
 
 impl<'a> Render<'a> for Image<'a> {
-    fn image(&self) -> &Image<'_> {
-        self
+    fn image(&self) -> Image<'_> {
+        *self
+    }
+}
+
+impl<'a> Render<'a> for ImageBuffer {
+    fn image(&self) -> Image<'_> {
+        self.as_image()
+    }
+}
+
+impl<'a> Render<'a> for &ImageBuffer {
+    fn image(&self) -> Image<'_> {
+        self.as_image()
     }
 }
 
 pub trait Render<'a> {
-    fn image(&self) -> &Image<'_>;
+    fn image(&self) -> Image<'_>;
+}
+
+/// An image owning its pixels, stored row by row starting at the top.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageBuffer {
+    pixels: Vec<Color>,
+    height: usize,
+    width: usize,
+}
+
+impl ImageBuffer {
+    /// Creates a black image.
+    pub fn new(height: usize, width: usize) -> Self {
+        Self::from_pixels(
+            vec![Color::new(0.0, 0.0, 0.0); height * width],
+            height,
+            width,
+        )
+    }
+
+    /// # Panics
+    /// If the number of pixels doesn't match the size.
+    pub fn from_pixels(pixels: Vec<Color>, height: usize, width: usize) -> Self {
+        assert!(pixels.len() == height * width, "incorrect pixel length");
+
+        Self {
+            pixels,
+            height,
+            width,
+        }
+    }
+
+    pub fn get_pixels(&self) -> &'_ [Color] {
+        &self.pixels
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    /// Borrows the buffer, e.g. to save it.
+    pub fn as_image(&self) -> Image<'_> {
+        Image::new(&self.pixels, self.height, self.width)
+    }
+
+    pub fn get_pixels_mut(&mut self) -> &'_ mut [Color] {
+        &mut self.pixels
+    }
+
+    pub fn into_pixels(self) -> Vec<Color> {
+        self.pixels
+    }
+
+    /// # Panics
+    /// If the coordinates are outside of the image.
+    pub fn get_pixel(&self, x: usize, y: usize) -> Color {
+        self[(x, y)]
+    }
+
+    /// # Panics
+    /// If the coordinates are outside of the image.
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        self[(x, y)] = color;
+    }
+
+    /// Iterates over the rows, starting at the top.
+    pub fn rows(&self) -> impl DoubleEndedIterator<Item = &[Color]> + '_ {
+        self.pixels.chunks_exact(self.width.max(1))
+    }
+
+    pub fn rows_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut [Color]> + '_ {
+        self.pixels.chunks_exact_mut(self.width.max(1))
+    }
+
+    /// Applies the function to every pixel.
+    pub fn map<F: Fn(Color) -> Color>(&self, f: F) -> Self {
+        Self::from_pixels(
+            self.pixels.iter().copied().map(f).collect(),
+            self.height,
+            self.width,
+        )
+    }
+
+    /// Copies the region with the top left corner at `(x, y)`.
+    ///
+    /// # Panics
+    /// If the region doesn't fit into the image.
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Self {
+        assert!(
+            x + width <= self.width && y + height <= self.height,
+            "crop region outside of the image"
+        );
+
+        let pixels = self
+            .rows()
+            .skip(y)
+            .take(height)
+            .flat_map(|row| row[x..x + width].iter().copied())
+            .collect();
+
+        Self::from_pixels(pixels, height, width)
+    }
+
+    /// Scales the image to the new size with bilinear filtering.
+    pub fn resize(&self, width: usize, height: usize) -> Self {
+        let mut res = Self::new(height, width);
+        if self.pixels.is_empty() {
+            return res;
+        }
+
+        // maps the pixel centers onto each other
+        let source = |dst: usize, dst_len: usize, src_len: usize| {
+            let pos = (dst as f64 + 0.5) * src_len as f64 / dst_len as f64 - 0.5;
+            let pos = pos.clamp(0.0, (src_len - 1) as f64);
+            let i = pos.floor() as usize;
+            (i, (i + 1).min(src_len - 1), pos - i as f64)
+        };
+
+        for y in 0..height {
+            let (y0, y1, ty) = source(y, height, self.height);
+
+            for x in 0..width {
+                let (x0, x1, tx) = source(x, width, self.width);
+
+                let top = self[(x0, y0)] * (1.0 - tx) + self[(x1, y0)] * tx;
+                let bottom = self[(x0, y1)] * (1.0 - tx) + self[(x1, y1)] * tx;
+                res[(x, y)] = top * (1.0 - ty) + bottom * ty;
+            }
+        }
+
+        res
+    }
+
+    /// Mirrors the image along the vertical axis.
+    pub fn flip_horizontal(&mut self) {
+        for row in self.rows_mut() {
+            row.reverse();
+        }
+    }
+
+    /// Mirrors the image along the horizontal axis.
+    pub fn flip_vertical(&mut self) {
+        let width = self.width;
+        for y in 0..self.height / 2 {
+            let (top, bottom) = self.pixels.split_at_mut((self.height - 1 - y) * width);
+            top[y * width..(y + 1) * width].swap_with_slice(&mut bottom[..width]);
+        }
+    }
+}
+
+impl Index<(usize, usize)> for ImageBuffer {
+    type Output = Color;
+
+    /// Indexes with `(x, y)`, starting at the top left.
+    fn index(&self, (x, y): (usize, usize)) -> &Self::Output {
+        assert!(
+            x < self.width && y < self.height,
+            "pixel outside of the image"
+        );
+        &self.pixels[y * self.width + x]
+    }
+}
+
+impl IndexMut<(usize, usize)> for ImageBuffer {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Self::Output {
+        assert!(
+            x < self.width && y < self.height,
+            "pixel outside of the image"
+        );
+        &mut self.pixels[y * self.width + x]
+    }
+}
+
+impl From<Image<'_>> for ImageBuffer {
+    fn from(img: Image<'_>) -> Self {
+        Self::from_pixels(img.get_pixels().to_vec(), img.get_height(), img.get_width())
+    }
+}
+
+impl<'a> From<&'a ImageBuffer> for Image<'a> {
+    fn from(buf: &'a ImageBuffer) -> Self {
+        buf.as_image()
+    }
+}
+
+/// Converts a value of the `0..256` range into an integer sample of the
+/// integer image formats, everything from 255 on is `max_value`.
+pub fn quantize(v: f64, max_value: u16) -> u16 {
+    let max = max_value as f64;
+    (v / 255.0 * max).round().clamp(0.0, max) as u16
+}
+
+/// The inverse of [`quantize`], `max_value` becomes 255.
+pub fn dequantize(sample: u16, max_value: u16) -> f64 {
+    sample as f64 * 255.0 / max_value as f64
+}
+
+/// Error while decoding an image file.
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    /// The file doesn't start with a known magic number.
+    UnsupportedFormat(String),
+    /// A header field is missing or malformed.
+    InvalidHeader(String),
+    /// A sample could not be parsed or is above the maximum value.
+    InvalidValue(String),
+    /// The file ends before all of the pixels are read.
+    UnexpectedEof,
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {}", err),
+            Self::UnsupportedFormat(s) => write!(f, "unsupported format '{}'", s),
+            Self::InvalidHeader(s) => write!(f, "invalid header field '{}'", s),
+            Self::InvalidValue(s) => write!(f, "invalid value '{}'", s),
+            Self::UnexpectedEof => write!(f, "unexpected end of file"),
+        }
+    }
+}
+
+impl error::Error for ImageError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImageError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Reads the whitespace separated tokens of a netpbm style header.
+pub(super) struct HeaderReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> HeaderReader<'a> {
+    pub(super) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Returns the next token, skipping `#` comments.
+    pub(super) fn token(&mut self) -> Result<&'a str, ImageError> {
+        loop {
+            match self.data.get(self.pos) {
+                Some(b'#') => {
+                    while !matches!(self.data.get(self.pos), Some(b'\n') | Some(b'\r') | None) {
+                        self.pos += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_whitespace() => self.pos += 1,
+                Some(_) => break,
+                None => return Err(ImageError::UnexpectedEof),
+            }
+        }
+
+        let start = self.pos;
+        while matches!(self.data.get(self.pos), Some(c) if !c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+
+        std::str::from_utf8(&self.data[start..self.pos]).map_err(|_| {
+            ImageError::InvalidHeader(
+                String::from_utf8_lossy(&self.data[start..self.pos]).to_string(),
+            )
+        })
+    }
+
+    /// Parses the next token, the error contains the field name.
+    pub(super) fn parse<T: std::str::FromStr>(&mut self, field: &str) -> Result<T, ImageError> {
+        let token = self.token()?;
+        token
+            .parse()
+            .map_err(|_| ImageError::InvalidHeader(format!("{} {}", field, token)))
+    }
+
+    /// Skips the single whitespace character ending the header and returns
+    /// the rest of the data.
+    pub(super) fn body(self) -> Result<&'a [u8], ImageError> {
+        match self.data.get(self.pos) {
+            Some(c) if c.is_ascii_whitespace() => Ok(&self.data[self.pos + 1..]),
+            _ => Err(ImageError::UnexpectedEof),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient() -> ImageBuffer {
+        let mut img = ImageBuffer::new(2, 3);
+        for y in 0..2 {
+            for x in 0..3 {
+                img.set_pixel(x, y, Color::new(x as f64, y as f64, 0.0));
+            }
+        }
+        img
+    }
+
+    #[test]
+    fn test_pixels() {
+        let img = gradient();
+
+        assert_eq!(img.get_pixel(2, 1), Color::new(2.0, 1.0, 0.0));
+        assert_eq!(img.get_pixels()[4], Color::new(1.0, 1.0, 0.0));
+        assert_eq!(img.rows().count(), 2);
+        assert!(img.rows().all(|r| r.len() == 3));
+
+        let copy = ImageBuffer::from(img.as_image());
+        assert_eq!(copy, img);
+    }
+
+    #[test]
+    fn test_crop_flip() {
+        let mut img = gradient();
+
+        let cropped = img.crop(1, 1, 2, 1);
+        assert_eq!(
+            cropped.get_pixels(),
+            &[Color::new(1.0, 1.0, 0.0), Color::new(2.0, 1.0, 0.0)]
+        );
+
+        img.flip_horizontal();
+        assert_eq!(img.get_pixel(0, 0), Color::new(2.0, 0.0, 0.0));
+
+        img.flip_vertical();
+        assert_eq!(img.get_pixel(0, 0), Color::new(2.0, 1.0, 0.0));
+        assert_eq!(img.get_pixel(2, 1), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_resize() {
+        let img = gradient();
+
+        // the same size keeps the pixels as they are
+        assert_eq!(img.resize(3, 2), img);
+
+        let big = img.resize(6, 4);
+        assert_eq!(big.get_pixel(0, 0), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(big.get_pixel(5, 3), Color::new(2.0, 1.0, 0.0));
+        assert_eq!(big.get_pixel(2, 1), Color::new(0.75, 0.25, 0.0));
+
+        let small = img.resize(1, 1);
+        assert_eq!(small.get_pixel(0, 0), Color::new(1.0, 0.5, 0.0));
+    }
 }
@@ -1,3 +1,9 @@
 mod image;
 pub use image::*;
+pub mod compare;
+pub mod exr;
+pub mod hdr;
+pub mod pfm;
+pub mod png;
 pub mod ppm;
+pub mod tonemap;
@@ -0,0 +1,166 @@
+//! Portable float map output, storing the linear values as 32 bit floats.
+
+use crate::render::{image::HeaderReader, Color, ImageBuffer, ImageError, Render};
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+/// Saves the linear pixel values as a little endian color PFM.
+pub fn save<'a, T: Render<'a>, P: AsRef<Path>>(image: T, path: P) -> Result<(), io::Error> {
+    let img = image.image();
+    let (width, height) = (img.get_width(), img.get_height());
+
+    let mut data = format!("PF\n{} {}\n-1.0\n", width, height).into_bytes();
+    data.reserve(width * height * 3 * 4);
+
+    // the rows are stored bottom to top
+    for row in img.get_pixels().chunks_exact(width.max(1)).rev() {
+        for p in row {
+            for c in [p.x(), p.y(), p.z()] {
+                data.extend_from_slice(&(c as f32).to_le_bytes());
+            }
+        }
+    }
+
+    let path = path.as_ref().to_string_lossy();
+
+    fs::write(
+        if path.ends_with(".pfm") {
+            path.to_string()
+        } else {
+            format!("{}.pfm", path)
+        },
+        data,
+    )
+}
+
+/// Loads a color or grayscale PFM with its linear values.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<ImageBuffer, ImageError> {
+    parse(fs::read(path)?.as_slice())
+}
+
+/// Parses a color (`PF`) or grayscale (`Pf`) PFM, grayscale values are
+/// copied into all three channels.
+pub fn parse<R: Read>(mut reader: R) -> Result<ImageBuffer, ImageError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let mut header = HeaderReader::new(&data);
+    let channels = match header.token()? {
+        "PF" => 3,
+        "Pf" => 1,
+        magic => return Err(ImageError::UnsupportedFormat(magic.to_string())),
+    };
+
+    let width: usize = header.parse("width")?;
+    let height: usize = header.parse("height")?;
+    // the sign of the scale gives the byte order
+    let scale: f32 = header.parse("scale")?;
+    if scale == 0.0 || !scale.is_finite() {
+        return Err(ImageError::InvalidHeader(format!("scale {}", scale)));
+    }
+
+    let body = header.body()?;
+    let len = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(channels * 4))
+        .ok_or_else(|| ImageError::InvalidHeader(format!("size {}x{}", width, height)))?;
+    if body.len() < len {
+        return Err(ImageError::UnexpectedEof);
+    }
+
+    let values: Vec<f64> = body[..len]
+        .chunks_exact(4)
+        .map(|b| {
+            let b = [b[0], b[1], b[2], b[3]];
+            let v = if scale < 0.0 {
+                f32::from_le_bytes(b)
+            } else {
+                f32::from_be_bytes(b)
+            };
+            v as f64
+        })
+        .collect();
+
+    let mut pixels = Vec::with_capacity(width * height);
+    // the rows are stored bottom to top
+    for row in values.chunks_exact((width * channels).max(1)).rev() {
+        pixels.extend(row.chunks_exact(channels).map(|c| match c {
+            [v] => Color::new(*v, *v, *v),
+            _ => Color::new(c[0], c[1], c[2]),
+        }));
+    }
+
+    Ok(ImageBuffer::from_pixels(pixels, height, width))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io};
+
+    use super::*;
+    use crate::render::Image;
+
+    #[test]
+    fn test_save() -> Result<(), io::Error> {
+        let px = [
+            Color::new(1.0, 2.0, 3.0),
+            Color::new(0.5, 0.0, 0.0),
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(100.0, 0.25, -1.0),
+        ];
+        let img = Image::new(&px, 2, 2);
+
+        let tmp = tempfile::Builder::new().suffix(".pfm").tempfile()?;
+        save(img, tmp.path())?;
+        let data = fs::read(tmp.path())?;
+
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&data[..header.len()], header);
+
+        let values: Vec<f32> = data[header.len()..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        assert_eq!(
+            values,
+            [0.0, 0.0, 0.0, 100.0, 0.25, -1.0, 1.0, 2.0, 3.0, 0.5, 0.0, 0.0]
+        );
+
+        let loaded = load(tmp.path()).unwrap();
+        assert_eq!(loaded.get_width(), 2);
+        assert_eq!(loaded.get_height(), 2);
+        assert_eq!(loaded.get_pixels(), &px);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_gray_big_endian() -> Result<(), ImageError> {
+        let mut data = b"Pf\n2 1\n1.0\n".to_vec();
+        for v in [0.5f32, 8.0] {
+            data.extend_from_slice(&v.to_be_bytes());
+        }
+
+        let img = parse(data.as_slice())?;
+        assert_eq!(
+            img.get_pixels(),
+            &[Color::new(0.5, 0.5, 0.5), Color::new(8.0, 8.0, 8.0)]
+        );
+
+        data.pop();
+        assert!(matches!(
+            parse(data.as_slice()),
+            Err(ImageError::UnexpectedEof)
+        ));
+
+        assert!(matches!(
+            parse(&b"PF\n4294967296 4294967296\n-1.0\n"[..]),
+            Err(ImageError::InvalidHeader(_))
+        ));
+
+        Ok(())
+    }
+}
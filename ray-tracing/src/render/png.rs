@@ -0,0 +1,1085 @@
+//! A self contained PNG encoder for RGB images, and a decoder for PNGs
+//! without interlacing.
+//!
+//! Every row is filtered with the filter giving the smallest sum of absolute
+//! differences, the result is compressed with LZ77 and the fixed deflate
+//! Huffman codes.
+
+use crate::render::{dequantize, quantize, Color, Image, ImageBuffer, ImageError, Render};
+use std::{fs, io, path::Path};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+}
+
+impl BitDepth {
+    fn bits(self) -> u8 {
+        match self {
+            Self::Eight => 8,
+            Self::Sixteen => 16,
+        }
+    }
+
+    fn bytes_per_pixel(self) -> usize {
+        3 * self.bits() as usize / 8
+    }
+}
+
+/// Saves the image as an 8 bit PNG.
+pub fn save<'a, T: Render<'a>, P: AsRef<Path>>(image: T, path: P) -> Result<(), io::Error> {
+    save_with_depth(image, path, BitDepth::Eight)
+}
+
+/// Saves the image as a PNG with the given bit depth.
+///
+/// The pixels are expected in the `0..256` range, the same as for
+/// [`ppm::save`](super::ppm::save).
+pub fn save_with_depth<'a, T: Render<'a>, P: AsRef<Path>>(
+    image: T,
+    path: P,
+    depth: BitDepth,
+) -> Result<(), io::Error> {
+    let data = encode(&image.image(), depth);
+
+    let path = path.as_ref().to_string_lossy();
+
+    fs::write(
+        if path.ends_with(".png") {
+            path.to_string()
+        } else {
+            format!("{}.png", path)
+        },
+        data,
+    )
+}
+
+/// Encodes the image into the bytes of a PNG file.
+pub fn encode(img: &Image<'_>, depth: BitDepth) -> Vec<u8> {
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(img.get_width() as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(img.get_height() as u32).to_be_bytes());
+    // bit depth, truecolor, deflate, adaptive filtering, no interlace
+    ihdr.extend_from_slice(&[depth.bits(), 2, 0, 0, 0]);
+
+    let raw = filter(&samples(img, depth), img.get_width(), depth);
+
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &ihdr);
+    write_chunk(&mut out, b"IDAT", &zlib(&raw));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// Converts the pixels into big endian samples.
+fn samples(img: &Image<'_>, depth: BitDepth) -> Vec<u8> {
+    let mut data = Vec::with_capacity(img.get_pixels().len() * depth.bytes_per_pixel());
+
+    for p in img.get_pixels() {
+        for c in [p.x(), p.y(), p.z()] {
+            match depth {
+                BitDepth::Eight => data.push(quantize(c, 255) as u8),
+                BitDepth::Sixteen => data.extend_from_slice(&quantize(c, 65535).to_be_bytes()),
+            }
+        }
+    }
+
+    data
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Prefixes every row with the best of the five PNG filters.
+fn filter(data: &[u8], width: usize, depth: BitDepth) -> Vec<u8> {
+    let bpp = depth.bytes_per_pixel();
+    let stride = width * bpp;
+
+    if stride == 0 {
+        return Vec::new();
+    }
+
+    let height = data.len() / stride;
+    let mut out = Vec::with_capacity(height * (stride + 1));
+    let mut candidate = vec![0; stride];
+    let mut best = vec![0; stride];
+    let zero = vec![0; stride];
+
+    for (j, row) in data.chunks_exact(stride).enumerate() {
+        let prev = if j == 0 {
+            &zero[..]
+        } else {
+            &data[(j - 1) * stride..j * stride]
+        };
+
+        let mut best_type = 0;
+        let mut best_cost = u64::MAX;
+
+        for ty in 0..5 {
+            for i in 0..stride {
+                let a = if i >= bpp { row[i - bpp] } else { 0 };
+                let b = prev[i];
+                let c = if i >= bpp { prev[i - bpp] } else { 0 };
+
+                let predicted = match ty {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    _ => paeth(a, b, c),
+                };
+                candidate[i] = row[i].wrapping_sub(predicted);
+            }
+
+            let cost = candidate
+                .iter()
+                .map(|&v| (v as i8).unsigned_abs() as u64)
+                .sum();
+            if cost < best_cost {
+                best_cost = cost;
+                best_type = ty;
+                std::mem::swap(&mut best, &mut candidate);
+            }
+        }
+
+        out.push(best_type);
+        out.extend_from_slice(&best);
+    }
+
+    out
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 == 1 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+
+    !data.iter().fold(!0, |crc: u32, &b| {
+        table[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+
+    let (mut a, mut b) = (1u32, 0u32);
+    // the sums can't overflow within 5552 bytes
+    for chunk in data.chunks(5552) {
+        for &v in chunk {
+            a += v as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+
+    (b << 16) | a
+}
+
+fn zlib(data: &[u8]) -> Vec<u8> {
+    // deflate with a 32K window, default compression level
+    let mut out = vec![0x78, 0x9c];
+    out.extend(deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    buf: u64,
+    len: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            out: Vec::new(),
+            buf: 0,
+            len: 0,
+        }
+    }
+
+    /// Writes the lowest `n` bits, least significant bit first.
+    fn bits(&mut self, value: u32, n: u32) {
+        self.buf |= (value as u64) << self.len;
+        self.len += n;
+
+        while self.len >= 8 {
+            self.out.push(self.buf as u8);
+            self.buf >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which is stored most significant bit first.
+    fn code(&mut self, code: u32, n: u32) {
+        self.bits(code.reverse_bits() >> (32 - n), n);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.out.push(self.buf as u8);
+        }
+        self.out
+    }
+}
+
+const WINDOW: usize = 1 << 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Writes a literal or length symbol with the fixed Huffman code.
+fn write_symbol(w: &mut BitWriter, sym: u32) {
+    match sym {
+        0..=143 => w.code(0x30 + sym, 8),
+        144..=255 => w.code(0x190 + sym - 144, 9),
+        256..=279 => w.code(sym - 256, 7),
+        _ => w.code(0xc0 + sym - 280, 8),
+    }
+}
+
+fn write_match(w: &mut BitWriter, len: usize, dist: usize) {
+    // the tables are sorted, so the last base not above the value is the code
+    let l = LENGTH_BASE
+        .iter()
+        .rposition(|&b| b as usize <= len)
+        .unwrap_or(0);
+    write_symbol(w, 257 + l as u32);
+    w.bits(
+        (len - LENGTH_BASE[l] as usize) as u32,
+        LENGTH_EXTRA[l] as u32,
+    );
+
+    let d = DIST_BASE
+        .iter()
+        .rposition(|&b| b as usize <= dist)
+        .unwrap_or(0);
+    w.code(d as u32, 5);
+    w.bits((dist - DIST_BASE[d] as usize) as u32, DIST_EXTRA[d] as u32);
+}
+
+fn hash(data: &[u8]) -> usize {
+    let v = (data[0] as u32) << 16 | (data[1] as u32) << 8 | data[2] as u32;
+    (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Compresses the data into a single fixed Huffman deflate block.
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut w = BitWriter::new();
+    // final block, fixed Huffman codes
+    w.bits(1, 1);
+    w.bits(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW];
+
+    let insert = |pos: usize, head: &mut [usize], prev: &mut [usize]| {
+        if pos + MIN_MATCH <= data.len() {
+            let h = hash(&data[pos..]);
+            prev[pos % WINDOW] = head[h];
+            head[h] = pos;
+        }
+    };
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best_len = 0;
+        let mut best_dist = 0;
+
+        if pos + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - pos);
+            let mut candidate = head[hash(&data[pos..])];
+
+            for _ in 0..MAX_CHAIN {
+                if candidate == usize::MAX || pos - candidate > WINDOW - 1 {
+                    break;
+                }
+
+                let len = data[candidate..]
+                    .iter()
+                    .zip(&data[pos..pos + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = pos - candidate;
+                    if len == max_len {
+                        break;
+                    }
+                }
+
+                let next = prev[candidate % WINDOW];
+                // older entries were overwritten by newer positions
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            write_match(&mut w, best_len, best_dist);
+            for p in pos..pos + best_len {
+                insert(p, &mut head, &mut prev);
+            }
+            pos += best_len;
+        } else {
+            write_symbol(&mut w, data[pos] as u32);
+            insert(pos, &mut head, &mut prev);
+            pos += 1;
+        }
+    }
+
+    write_symbol(&mut w, 256);
+    w.finish()
+}
+
+/// Loads a PNG image, the samples are scaled back with
+/// [`dequantize`] so that white is 255 at every bit depth.
+///
+/// All bit depths and color types are read, the alpha channel is dropped.
+/// Interlaced images aren't supported.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<ImageBuffer, ImageError> {
+    decode(&fs::read(path)?)
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    depth: u8,
+    color_type: u8,
+    channels: usize,
+}
+
+impl Header {
+    fn parse(body: &[u8]) -> Result<Self, ImageError> {
+        if body.len() != 13 {
+            return Err(ImageError::InvalidHeader("IHDR".to_string()));
+        }
+
+        let dimension = |i: usize, field: &str| {
+            let bytes = [body[i], body[i + 1], body[i + 2], body[i + 3]];
+            match u32::from_be_bytes(bytes) {
+                0 => Err(ImageError::InvalidHeader(field.to_string())),
+                v => Ok(v as usize),
+            }
+        };
+        let width = dimension(0, "width")?;
+        let height = dimension(4, "height")?;
+        let (depth, color_type) = (body[8], body[9]);
+
+        let (channels, depths): (usize, &[u8]) = match color_type {
+            0 => (1, &[1, 2, 4, 8, 16]),
+            2 => (3, &[8, 16]),
+            3 => (1, &[1, 2, 4, 8]),
+            4 => (2, &[8, 16]),
+            6 => (4, &[8, 16]),
+            _ => {
+                return Err(ImageError::InvalidHeader(format!(
+                    "color type {}",
+                    color_type
+                )))
+            }
+        };
+        if !depths.contains(&depth) {
+            return Err(ImageError::InvalidHeader(format!("bit depth {}", depth)));
+        }
+        if body[10] != 0 || body[11] != 0 {
+            return Err(ImageError::InvalidHeader("compression method".to_string()));
+        }
+        match body[12] {
+            0 => {}
+            1 => return Err(ImageError::UnsupportedFormat("interlaced PNG".to_string())),
+            _ => return Err(ImageError::InvalidHeader("interlace method".to_string())),
+        }
+
+        Ok(Self {
+            width,
+            height,
+            depth,
+            color_type,
+            channels,
+        })
+    }
+
+    /// Bytes of a row without the filter type.
+    fn stride(&self) -> Result<usize, ImageError> {
+        self.width
+            .checked_mul(self.channels * self.depth as usize)
+            .map(|bits| bits.div_ceil(8))
+            .ok_or_else(|| ImageError::InvalidHeader("width".to_string()))
+    }
+
+    /// Bytes of the filtered image data.
+    fn raw_len(&self) -> Result<usize, ImageError> {
+        (self.stride()? + 1)
+            .checked_mul(self.height)
+            .ok_or_else(|| ImageError::InvalidHeader("height".to_string()))
+    }
+
+    /// Distance to the corresponding byte of the previous pixel.
+    fn filter_distance(&self) -> usize {
+        (self.channels * self.depth as usize / 8).max(1)
+    }
+}
+
+/// Decodes the bytes of a PNG file.
+pub fn decode(data: &[u8]) -> Result<ImageBuffer, ImageError> {
+    if !data.starts_with(&SIGNATURE) {
+        let magic = &data[..data.len().min(SIGNATURE.len())];
+        return Err(ImageError::UnsupportedFormat(
+            String::from_utf8_lossy(magic).to_string(),
+        ));
+    }
+
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut idat = Vec::new();
+
+    let mut pos = SIGNATURE.len();
+    loop {
+        let len = data
+            .get(pos..pos + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or(ImageError::UnexpectedEof)? as usize;
+        let chunk = data
+            .get(pos + 4..pos + 8 + len)
+            .ok_or(ImageError::UnexpectedEof)?;
+        let crc = data
+            .get(pos + 8 + len..pos + 12 + len)
+            .ok_or(ImageError::UnexpectedEof)?;
+        pos += 12 + len;
+
+        let (kind, body) = chunk.split_at(4);
+        if crc != crc32(chunk).to_be_bytes() {
+            return Err(ImageError::InvalidValue(format!(
+                "{} checksum",
+                String::from_utf8_lossy(kind)
+            )));
+        }
+
+        match kind {
+            b"IHDR" => header = Some(Header::parse(body)?),
+            b"PLTE" => palette = body.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect(),
+            b"IDAT" => idat.extend_from_slice(body),
+            b"IEND" => break,
+            // ancillary chunks like the gamma or transparency are ignored
+            _ => {}
+        }
+    }
+
+    let header = header.ok_or_else(|| ImageError::InvalidHeader("IHDR".to_string()))?;
+    // the header is checked before anything of its size is allocated
+    let stride = header.stride()?;
+    let raw_len = header.raw_len()?;
+    let raw = unzlib(&idat, raw_len)?;
+    if raw.len() < raw_len {
+        return Err(ImageError::UnexpectedEof);
+    }
+    let rows = unfilter(&raw, stride, header.filter_distance(), header.height)?;
+
+    let depth = header.depth;
+    // the largest sample is white at every bit depth
+    let max_value = ((1u32 << depth) - 1) as u16;
+    let mut pixels = Vec::with_capacity(header.width * header.height);
+
+    for row in rows.chunks_exact(stride) {
+        for x in 0..header.width {
+            let s = |c: usize| dequantize(sample(row, x * header.channels + c, depth), max_value);
+
+            pixels.push(match header.color_type {
+                0 | 4 => Color::new(s(0), s(0), s(0)),
+                2 | 6 => Color::new(s(0), s(1), s(2)),
+                _ => {
+                    let i = sample(row, x, depth) as usize;
+                    let [r, g, b] = *palette
+                        .get(i)
+                        .ok_or_else(|| ImageError::InvalidValue(format!("palette index {}", i)))?;
+                    Color::new(r as f64, g as f64, b as f64)
+                }
+            });
+        }
+    }
+
+    Ok(ImageBuffer::from_pixels(
+        pixels,
+        header.height,
+        header.width,
+    ))
+}
+
+/// The `index`th sample of a row, the ones below 8 bits are packed from the
+/// most significant bit on.
+fn sample(row: &[u8], index: usize, depth: u8) -> u16 {
+    match depth {
+        16 => u16::from_be_bytes([row[2 * index], row[2 * index + 1]]),
+        8 => row[index] as u16,
+        _ => {
+            let per_byte = 8 / depth as usize;
+            let shift = 8 - depth as usize * (index % per_byte + 1);
+            ((row[index / per_byte] >> shift) & ((1 << depth) - 1)) as u16
+        }
+    }
+}
+
+/// Reverses the filters of the rows, `distance` is the byte distance to the
+/// previous pixel.
+fn unfilter(
+    data: &[u8],
+    stride: usize,
+    distance: usize,
+    height: usize,
+) -> Result<Vec<u8>, ImageError> {
+    let mut out = vec![0u8; stride * height];
+
+    for (y, line) in data.chunks_exact(stride + 1).take(height).enumerate() {
+        let (kind, line) = (line[0], &line[1..]);
+        let (done, rest) = out.split_at_mut(y * stride);
+        let prior = if y == 0 {
+            None
+        } else {
+            Some(&done[(y - 1) * stride..])
+        };
+        let cur = &mut rest[..stride];
+
+        for i in 0..stride {
+            let a = if i >= distance { cur[i - distance] } else { 0 };
+            let b = prior.map_or(0, |p| p[i]);
+            let c = match prior {
+                Some(p) if i >= distance => p[i - distance],
+                _ => 0,
+            };
+
+            let predicted = match kind {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(ImageError::InvalidValue(format!("filter type {}", kind))),
+            };
+            cur[i] = line[i].wrapping_add(predicted);
+        }
+    }
+
+    Ok(out)
+}
+
+/// Decompresses a zlib stream of at most `limit` bytes.
+fn unzlib(data: &[u8], limit: usize) -> Result<Vec<u8>, ImageError> {
+    let (cmf, flg) = match data {
+        [cmf, flg, ..] => (*cmf, *flg),
+        _ => return Err(ImageError::UnexpectedEof),
+    };
+    // deflate, a valid check value and no preset dictionary
+    if cmf & 0x0f != 8 || !((cmf as u16) << 8 | flg as u16).is_multiple_of(31) || flg & 0x20 != 0 {
+        return Err(ImageError::InvalidValue("zlib header".to_string()));
+    }
+
+    let (out, used) = inflate(&data[2..], limit)?;
+    let checksum = data
+        .get(2 + used..2 + used + 4)
+        .ok_or(ImageError::UnexpectedEof)?;
+    if checksum != adler32(&out).to_be_bytes() {
+        return Err(ImageError::InvalidValue("zlib checksum".to_string()));
+    }
+
+    Ok(out)
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    /// Position in bits.
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bit(&mut self) -> Result<u32, ImageError> {
+        let byte = *self
+            .data
+            .get(self.pos / 8)
+            .ok_or(ImageError::UnexpectedEof)?;
+        let bit = (byte >> (self.pos % 8)) & 1;
+        self.pos += 1;
+        Ok(bit as u32)
+    }
+
+    /// Reads `n` bits, least significant bit first.
+    fn bits(&mut self, n: u32) -> Result<u32, ImageError> {
+        let mut value = 0;
+        for i in 0..n {
+            value |= self.bit()? << i;
+        }
+        Ok(value)
+    }
+
+    /// Skips to the next byte boundary and returns the byte position.
+    fn align(&mut self) -> usize {
+        self.pos = self.pos.div_ceil(8) * 8;
+        self.pos / 8
+    }
+}
+
+/// A canonical Huffman code, given by the number of codes of every length
+/// and the symbols ordered by their code.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, ImageError> {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // incomplete codes are fine, more codes than fit aren't
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(ImageError::InvalidValue("huffman code lengths".to_string()));
+            }
+        }
+
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, r: &mut BitReader<'_>) -> Result<u16, ImageError> {
+        // the first code and the index of its symbol of the current length
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+
+        for &count in &self.counts[1..] {
+            code |= r.bit()? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(ImageError::InvalidValue("huffman code".to_string()))
+    }
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman), ImageError> {
+    let mut lengths = [8u8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_codes(r: &mut BitReader<'_>) -> Result<(Huffman, Huffman), ImageError> {
+    const ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+
+    let hlit = r.bits(5)? as usize + 257;
+    let hdist = r.bits(5)? as usize + 1;
+    let hclen = r.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &i in &ORDER[..hclen] {
+        code_lengths[i] = r.bits(3)? as u8;
+    }
+    let code = Huffman::new(&code_lengths)?;
+
+    let invalid = || ImageError::InvalidValue("huffman code lengths".to_string());
+    let mut lengths = Vec::with_capacity(hlit + hdist);
+    while lengths.len() < hlit + hdist {
+        let (len, repeat) = match code.decode(r)? {
+            sym @ 0..=15 => (sym as u8, 1),
+            16 => (*lengths.last().ok_or_else(invalid)?, 3 + r.bits(2)?),
+            17 => (0, 3 + r.bits(3)?),
+            18 => (0, 11 + r.bits(7)?),
+            _ => return Err(invalid()),
+        };
+        lengths.extend((0..repeat).map(|_| len));
+    }
+    if lengths.len() > hlit + hdist || lengths[256] == 0 {
+        return Err(invalid());
+    }
+
+    Ok((
+        Huffman::new(&lengths[..hlit])?,
+        Huffman::new(&lengths[hlit..])?,
+    ))
+}
+
+/// Decompresses a deflate stream, returns the data and the number of bytes
+/// read. Fails as soon as the data grows beyond `limit` bytes.
+fn inflate(data: &[u8], limit: usize) -> Result<(Vec<u8>, usize), ImageError> {
+    let mut r = BitReader::new(data);
+    let mut out = Vec::new();
+    let too_long = || ImageError::InvalidValue("deflate data length".to_string());
+
+    loop {
+        let last = r.bit()? == 1;
+
+        let (lit, dist) = match r.bits(2)? {
+            0 => {
+                let p = r.align();
+                let header = data.get(p..p + 4).ok_or(ImageError::UnexpectedEof)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                let nlen = u16::from_le_bytes([header[2], header[3]]);
+                if len != !nlen {
+                    return Err(ImageError::InvalidValue("stored block length".to_string()));
+                }
+
+                let end = p + 4 + len as usize;
+                if out.len() + len as usize > limit {
+                    return Err(too_long());
+                }
+                out.extend_from_slice(data.get(p + 4..end).ok_or(ImageError::UnexpectedEof)?);
+                r.pos = end * 8;
+
+                if last {
+                    break;
+                }
+                continue;
+            }
+            1 => fixed_codes()?,
+            2 => dynamic_codes(&mut r)?,
+            _ => return Err(ImageError::InvalidValue("block type".to_string())),
+        };
+
+        loop {
+            let sym = lit.decode(&mut r)? as usize;
+            match sym {
+                0..=255 if out.len() >= limit => return Err(too_long()),
+                0..=255 => out.push(sym as u8),
+                256 => break,
+                _ => {
+                    let l = sym - 257;
+                    if l >= LENGTH_BASE.len() {
+                        return Err(ImageError::InvalidValue("length code".to_string()));
+                    }
+                    let len = LENGTH_BASE[l] as usize + r.bits(LENGTH_EXTRA[l] as u32)? as usize;
+
+                    let d = dist.decode(&mut r)? as usize;
+                    if d >= DIST_BASE.len() {
+                        return Err(ImageError::InvalidValue("distance code".to_string()));
+                    }
+                    let distance = DIST_BASE[d] as usize + r.bits(DIST_EXTRA[d] as u32)? as usize;
+                    if distance > out.len() {
+                        return Err(ImageError::InvalidValue("distance".to_string()));
+                    }
+                    if out.len() + len > limit {
+                        return Err(too_long());
+                    }
+
+                    // the copy may overlap with its own output
+                    let start = out.len() - distance;
+                    for i in 0..len {
+                        out.push(out[start + i]);
+                    }
+                }
+            }
+        }
+
+        if last {
+            break;
+        }
+    }
+
+    Ok((out, r.align()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use crate::render::Color;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_filter() {
+        // a gradient is turned into constant differences by the sub filter
+        let data: Vec<u8> = (0..12).map(|i| i * 10).collect();
+        let res = filter(&data, 4, BitDepth::Eight);
+
+        assert_eq!(res[0], 1);
+        assert_eq!(&res[1..], &[0, 10, 20, 30, 30, 30, 30, 30, 30, 30, 30, 30]);
+    }
+
+    #[test]
+    fn test_save() -> Result<(), io::Error> {
+        let px: Vec<_> = (0..64)
+            .map(|i| Color::new(i as f64 * 4.0, 128.0, 255.5))
+            .collect();
+        let img = Image::new(&px, 8, 8);
+
+        let tmp = tempfile::Builder::new().suffix(".png").tempfile()?;
+        save_with_depth(img, tmp.path(), BitDepth::Sixteen)?;
+        let data = fs::read(tmp.path())?;
+
+        assert_eq!(&data[..8], &SIGNATURE);
+        assert_eq!(&data[8..16], b"\0\0\0\x0dIHDR");
+        assert_eq!(&data[16..29], &[0, 0, 0, 8, 0, 0, 0, 8, 16, 2, 0, 0, 0]);
+        assert_eq!(&data[data.len() - 12..], b"\0\0\0\0IEND\xae\x42\x60\x82");
+
+        // every chunk ends with the checksum of its type and data
+        let mut i = 8;
+        while i < data.len() {
+            let len = u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]) as usize;
+            let crc = &data[i + 8 + len..i + 12 + len];
+            assert_eq!(crc, &crc32(&data[i + 4..i + 8 + len]).to_be_bytes());
+            i += 12 + len;
+        }
+        assert_eq!(i, data.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inflate() {
+        let stored = [
+            120, 1, 1, 6, 0, 249, 255, 115, 116, 111, 114, 101, 100, 9, 60, 2, 146,
+        ];
+        assert_eq!(unzlib(&stored, 6).unwrap(), b"stored");
+
+        // compressed by zlib with dynamic Huffman codes
+        #[rustfmt::skip]
+        let dynamic = [
+            0x78, 0xda, 0x45, 0x50, 0x81, 0x11, 0xc4, 0x30, 0x08, 0x9a, 0x15, 0x64, 0x08, 0xc6,
+            0x7f, 0xc1, 0xf4, 0xbe, 0xd7, 0x24, 0xc6, 0x80, 0xa0, 0xd0, 0x90, 0x26, 0x40, 0x18,
+            0x80, 0x07, 0x43, 0xc8, 0xb9, 0x43, 0xcc, 0x66, 0x0b, 0x03, 0x72, 0x17, 0xc6, 0xe0,
+            0xbe, 0x9b, 0x7d, 0xe2, 0x7e, 0x5a, 0x12, 0x24, 0x05, 0xbd, 0x51, 0x4b, 0x81, 0xdd,
+            0xc2, 0xea, 0xc9, 0x90, 0x52, 0x3d, 0x10, 0x8d, 0xa0, 0x88, 0xb4, 0x68, 0xe1, 0xaa,
+            0x7c, 0x81, 0xf3, 0xe2, 0xa0, 0x83, 0xd8, 0x60, 0xb4, 0xb9, 0xfd, 0x19, 0xdd, 0xd8,
+            0x74, 0x8d, 0xf8, 0x69, 0xa1, 0x12, 0xbb, 0x34, 0x77, 0x65, 0xdd, 0x6b, 0xf1, 0x91,
+            0x4a, 0xce, 0xc9, 0xe9, 0xea, 0xdb, 0x78, 0xbd, 0x39, 0x96, 0xd6, 0xf8, 0x59, 0x48,
+            0x76, 0xce, 0xf9, 0x57, 0xb6, 0x62, 0xb8, 0x8b, 0xae, 0xa7, 0xd7, 0x1b, 0xf1, 0x46,
+            0xd6, 0xd6, 0xe6, 0x73, 0x90, 0xf1, 0xdc, 0xe4, 0xd2, 0x66, 0xe9, 0x99, 0x0c, 0x1f,
+            0xf0, 0x31, 0xd2, 0x01, 0xff, 0xb6, 0xeb, 0xa5, 0xc3, 0x9a, 0x59, 0xd2, 0x0f, 0x42,
+            0x39, 0x9b, 0xc7,
+        ];
+        let mut x: u32 = 1;
+        let expected: Vec<u8> = (0..400)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345) & 0x7fff_ffff;
+                b"aaaaaabbbcdx"[(x >> 16) as usize % 12]
+            })
+            .collect();
+        assert_eq!(unzlib(&dynamic, 400).unwrap(), expected);
+
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * i % 251) as u8).collect();
+        let mut compressed = zlib(&data);
+        assert_eq!(unzlib(&compressed, usize::MAX).unwrap(), data);
+
+        // no more than the image needs is inflated
+        for (data, limit) in [
+            (&stored[..], 5),
+            (&dynamic[..], 399),
+            (&compressed[..], 999),
+        ] {
+            assert_eq!(
+                unzlib(data, limit).unwrap_err().to_string(),
+                "invalid value 'deflate data length'"
+            );
+        }
+
+        let last = compressed.len() - 1;
+        compressed[last] ^= 1;
+        assert!(matches!(
+            unzlib(&compressed, usize::MAX),
+            Err(ImageError::InvalidValue(_))
+        ));
+        assert!(matches!(
+            unzlib(&dynamic[..80], usize::MAX),
+            Err(ImageError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn test_decode() -> Result<(), ImageError> {
+        let px: Vec<_> = (0..12)
+            .map(|i| Color::new(i as f64 * 20.0, 255.0 - i as f64, 7.5))
+            .collect();
+        let img = Image::new(&px, 3, 4);
+
+        let eight = decode(&encode(&img, BitDepth::Eight))?;
+        assert_eq!((eight.get_width(), eight.get_height()), (4, 3));
+        for (l, r) in eight.get_pixels().iter().zip(&px) {
+            assert_eq!(*l, Color::new(r.x().round(), r.y().round(), r.z().round()));
+        }
+
+        let sixteen = decode(&encode(&img, BitDepth::Sixteen))?;
+        for (l, r) in sixteen.get_pixels().iter().zip(&px) {
+            assert!((*l - *r).length() < 0.01, "{:?} {:?}", l, r);
+        }
+
+        // white is the largest 16 bit value
+        let white = [Color::new(255.999, 255.999, 255.999)];
+        let data = encode(&Image::new(&white, 1, 1), BitDepth::Sixteen);
+        // the IDAT data after the signature, IHDR and the chunk header
+        let raw = unzlib(&data[41..data.len() - 16], 7).unwrap();
+        assert_eq!(&raw[1..3], &[0xff, 0xff]);
+        assert_eq!(decode(&data)?.get_pixel(0, 0).x(), 255.0);
+
+        // a 2 bit palette image, with a row of each filter type
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&5u32.to_be_bytes());
+        ihdr.extend_from_slice(&2u32.to_be_bytes());
+        ihdr.extend_from_slice(&[2, 3, 0, 0, 0]);
+        // indices 0 1 2 3 0 and 3 2 1 0 3, the second row with the up filter
+        let raw = [0, 0b0001_1011, 0, 2, 0b1110_0100 - 0b0001_1011, 0b1100_0000];
+
+        let mut data = SIGNATURE.to_vec();
+        write_chunk(&mut data, b"IHDR", &ihdr);
+        write_chunk(
+            &mut data,
+            b"PLTE",
+            &[0, 0, 0, 10, 20, 30, 40, 50, 60, 255, 255, 255],
+        );
+        write_chunk(&mut data, b"IDAT", &zlib(&raw));
+        write_chunk(&mut data, b"IEND", &[]);
+
+        let palette = decode(&data)?;
+        let c = |i: f64| Color::new(i * 10.0, i * 10.0 + 10.0, i * 10.0 + 20.0);
+        assert_eq!(palette.get_pixel(1, 0), c(1.0));
+        assert_eq!(palette.get_pixel(4, 0), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(palette.get_pixel(0, 1), Color::new(255.0, 255.0, 255.0));
+        assert_eq!(palette.get_pixel(2, 1), c(1.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_gray() -> Result<(), ImageError> {
+        use crate::texture::{ColorSpace, ImageTexture};
+
+        let gray = |depth: u8, raw: &[u8]| {
+            let mut ihdr = Vec::new();
+            ihdr.extend_from_slice(&2u32.to_be_bytes());
+            ihdr.extend_from_slice(&1u32.to_be_bytes());
+            ihdr.extend_from_slice(&[depth, 0, 0, 0, 0]);
+
+            let mut data = SIGNATURE.to_vec();
+            write_chunk(&mut data, b"IHDR", &ihdr);
+            write_chunk(&mut data, b"IDAT", &zlib(raw));
+            write_chunk(&mut data, b"IEND", &[]);
+            data
+        };
+
+        // black and white at either end of the bit depths
+        for data in [gray(1, &[0, 0b0100_0000]), gray(16, &[0, 0, 0, 0xff, 0xff])] {
+            let img = decode(&data)?;
+            assert_eq!(img.get_pixel(1, 0), Color::new(255.0, 255.0, 255.0));
+
+            let tex = ImageTexture::from_ldr(&img, ColorSpace::Srgb);
+            assert_eq!(tex.image().get_pixel(0, 0), Color::new(0.0, 0.0, 0.0));
+            assert_eq!(tex.image().get_pixel(1, 0), Color::new(1.0, 1.0, 1.0));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let px = [Color::new(1.0, 2.0, 3.0)];
+        let valid = encode(&Image::new(&px, 1, 1), BitDepth::Eight);
+        let err = |data: &[u8]| decode(data).unwrap_err().to_string();
+
+        assert_eq!(err(b"P6 1 1 255"), "unsupported format 'P6 1 1 2'");
+        assert_eq!(err(&valid[..20]), "unexpected end of file");
+
+        let mut interlaced = valid.clone();
+        interlaced[28] = 1;
+        assert_eq!(err(&interlaced), "invalid value 'IHDR checksum'");
+        let crc = crc32(&interlaced[12..29]).to_be_bytes();
+        interlaced[29..33].copy_from_slice(&crc);
+        assert_eq!(err(&interlaced), "unsupported format 'interlaced PNG'");
+
+        // a size that overflows, before anything is inflated
+        let mut ihdr = [0xff; 8].to_vec();
+        ihdr.extend_from_slice(&[16, 6, 0, 0, 0]);
+        let mut huge = SIGNATURE.to_vec();
+        write_chunk(&mut huge, b"IHDR", &ihdr);
+        write_chunk(&mut huge, b"IDAT", &[]);
+        write_chunk(&mut huge, b"IEND", &[]);
+        assert_eq!(err(&huge), "invalid header field 'height'");
+    }
+}
@@ -1,46 +1,206 @@
-use crate::render::Render;
-use std::{fmt::Write, fs, io, path::Path};
+use crate::render::{
+    dequantize, image::HeaderReader, quantize, Color, ImageBuffer, ImageError, Render,
+};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::Path,
+};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Plain text `P3` file.
+    Ascii,
+    /// Raw `P6` file.
+    Binary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    pub encoding: Encoding,
+    /// Largest sample value, values above 255 are stored as two bytes in
+    /// the binary encoding.
+    pub max_value: u16,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            encoding: Encoding::Ascii,
+            max_value: 255,
+        }
+    }
+}
+
+impl Options {
+    pub fn new(encoding: Encoding, max_value: u16) -> Self {
+        Self {
+            encoding,
+            max_value,
+        }
+    }
+}
+
+/// Saves the image as a plain text PPM with a maximum value of 255.
 pub fn save<'a, T: Render<'a>, P: AsRef<Path>>(image: T, path: P) -> Result<(), io::Error> {
+    save_with(image, path, Options::default())
+}
+
+/// Saves the image with the given options, the `.ppm` extension is added
+/// if it is missing.
+pub fn save_with<'a, T: Render<'a>, P: AsRef<Path>>(
+    image: T,
+    path: P,
+    options: Options,
+) -> Result<(), io::Error> {
+    let path = path.as_ref().to_string_lossy();
+
+    let file = File::create(if path.ends_with(".ppm") {
+        path.to_string()
+    } else {
+        format!("{}.ppm", path)
+    })?;
+
+    let mut writer = BufWriter::new(file);
+    save_to(image, &mut writer, options)?;
+    writer.flush()
+}
+
+/// Writes the image row by row into the writer.
+///
+/// The pixels are expected in the `0..256` range and are scaled to the
+/// maximum value of the options.
+pub fn save_to<'a, T: Render<'a>, W: Write>(
+    image: T,
+    mut writer: W,
+    options: Options,
+) -> Result<(), io::Error> {
     let img = image.image();
+    let max_value = options.max_value;
 
-    const LINE_LENGTH: usize = 3 * 3 + 3; // => 255 255 255\n
+    if max_value == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the maximum value has to be at least 1",
+        ));
+    }
 
-    let text_length = {
-        let mut len = 3; // P3\n
-        len += ((img.get_width() as f64).log10() + 1.0) as usize;
-        len += 1;
-        len += ((img.get_height() as f64).log10() + 1.0) as usize;
-        len += 5; // => \n255\n
-        len += img.get_height() + img.get_height() * img.get_width() * LINE_LENGTH;
-        len
+    let magic = match options.encoding {
+        Encoding::Ascii => "P3",
+        Encoding::Binary => "P6",
     };
+    write!(
+        writer,
+        "{}\n{} {}\n{}\n",
+        magic,
+        img.get_width(),
+        img.get_height(),
+        max_value
+    )?;
+
+    let sample = |v: f64| quantize(v, max_value);
 
-    let mut s = String::with_capacity(text_length);
+    let mut line = Vec::new();
+    for row in img.get_pixels().chunks_exact(img.get_width().max(1)) {
+        line.clear();
 
-    // no error possible as per docs
-    write!(s, "P3\n{} {}\n255\n", img.get_width(), img.get_height()).unwrap();
+        for p in row {
+            let (r, g, b) = (sample(p.x()), sample(p.y()), sample(p.z()));
 
-    for p in img.get_pixels() {
-        let r = p.x() as u8;
-        let g = p.y() as u8;
-        let b = p.z() as u8;
+            match options.encoding {
+                Encoding::Ascii => writeln!(line, "{} {} {}", r, g, b)?,
+                Encoding::Binary if max_value < 256 => line.extend([r as u8, g as u8, b as u8]),
+                Encoding::Binary => {
+                    for v in [r, g, b] {
+                        line.extend_from_slice(&v.to_be_bytes());
+                    }
+                }
+            }
+        }
 
-        // no error possible as per docs
-        let _ = write!(s, "{} {} {}\n", r, g, b);
+        writer.write_all(&line)?;
     }
 
-    let path = path.as_ref().to_string_lossy();
+    Ok(())
+}
 
-    // write file content
-    fs::write(
-        if path.ends_with(".ppm") {
-            path.to_string()
-        } else {
-            format!("{}.ppm", path)
-        },
-        &s,
-    )
+/// Loads a `P3` or `P6` file with any maximum value.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<ImageBuffer, ImageError> {
+    parse(fs::read(path)?.as_slice())
+}
+
+/// Parses a `P3` or `P6` image, the samples are scaled back with
+/// [`dequantize`] so that the maximum value is 255.
+pub fn parse<R: Read>(mut reader: R) -> Result<ImageBuffer, ImageError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let mut header = HeaderReader::new(&data);
+    let encoding = match header.token()? {
+        "P3" => Encoding::Ascii,
+        "P6" => Encoding::Binary,
+        magic => return Err(ImageError::UnsupportedFormat(magic.to_string())),
+    };
+
+    let width: usize = header.parse("width")?;
+    let height: usize = header.parse("height")?;
+    let max_value: u16 = header.parse("maximum value")?;
+    if max_value == 0 {
+        return Err(ImageError::InvalidHeader("maximum value 0".to_string()));
+    }
+
+    let count = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(3))
+        .ok_or_else(|| ImageError::InvalidHeader(format!("size {}x{}", width, height)))?;
+    // grows with the data, the header alone can't be trusted with the size
+    let mut samples = Vec::new();
+
+    match encoding {
+        Encoding::Ascii => {
+            for _ in 0..count {
+                let token = header.token()?;
+                let v = token
+                    .parse::<u16>()
+                    .map_err(|_| ImageError::InvalidValue(token.to_string()))?;
+                samples.push(v);
+            }
+        }
+        Encoding::Binary => {
+            let body = header.body()?;
+            let size = if max_value < 256 { 1 } else { 2 };
+            let len = count
+                .checked_mul(size)
+                .ok_or_else(|| ImageError::InvalidHeader(format!("size {}x{}", width, height)))?;
+
+            if body.len() < len {
+                return Err(ImageError::UnexpectedEof);
+            }
+
+            samples.extend(body[..len].chunks_exact(size).map(|b| match b {
+                [v] => *v as u16,
+                _ => u16::from_be_bytes([b[0], b[1]]),
+            }));
+        }
+    }
+
+    if let Some(v) = samples.iter().find(|&&v| v > max_value) {
+        return Err(ImageError::InvalidValue(v.to_string()));
+    }
+
+    // the inverse of the scaling done while saving
+    let pixels = samples
+        .chunks_exact(3)
+        .map(|c| {
+            Color::new(
+                dequantize(c[0], max_value),
+                dequantize(c[1], max_value),
+                dequantize(c[2], max_value),
+            )
+        })
+        .collect();
+
+    Ok(ImageBuffer::from_pixels(pixels, height, width))
 }
 
 #[cfg(test)]
@@ -51,7 +211,10 @@ mod tests {
     };
     use tempfile;
 
-    use crate::render::{ppm::save, Color, Image};
+    use crate::render::{
+        ppm::{load, parse, save, save_to, Encoding, Options},
+        Color, Image, ImageError,
+    };
 
     #[test]
     fn test_rainbow() -> Result<(), io::Error> {
@@ -62,7 +225,7 @@ mod tests {
 
         let mut res = format!("P3\n{} {}\n255\n", IMAGE_WIDTH, IMAGE_HEIGHT);
 
-        let per = |v, c| ((v as f64) / ((c - 1) as f64));
+        let per = |v, c| (v as f64) / ((c - 1) as f64);
 
         let rcon = |v| 255.999 * v;
         let con = |v| rcon(v) as u8;
@@ -105,4 +268,85 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_binary() -> Result<(), io::Error> {
+        let px = [Color::new(0.0, 128.0, 255.9), Color::new(1.5, 64.25, 300.0)];
+        let img = || Image::new(&px, 1, 2);
+
+        let mut buf = Vec::new();
+        save_to(img(), &mut buf, Options::new(Encoding::Binary, 255))?;
+        assert_eq!(buf, b"P6\n2 1\n255\n\x00\x80\xff\x02\x40\xff");
+
+        let mut buf = Vec::new();
+        save_to(img(), &mut buf, Options::new(Encoding::Binary, 65535))?;
+        let mut expected = b"P6\n2 1\n65535\n".to_vec();
+        for v in [0u16, 32896, 65535, 386, 16512, 65535] {
+            expected.extend_from_slice(&v.to_be_bytes());
+        }
+        assert_eq!(buf, expected);
+
+        let mut buf = Vec::new();
+        save_to(img(), &mut buf, Options::new(Encoding::Ascii, 15))?;
+        assert_eq!(buf, b"P3\n2 1\n15\n0 8 15\n0 4 15\n");
+
+        let err = save_to(img(), io::sink(), Options::new(Encoding::Ascii, 0));
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load() -> Result<(), ImageError> {
+        let px: Vec<_> = (0..6)
+            .map(|i| Color::new(i as f64 * 40.0, 255.0 - i as f64, 7.0))
+            .collect();
+
+        for options in [
+            Options::new(Encoding::Ascii, 255),
+            Options::new(Encoding::Binary, 255),
+            Options::new(Encoding::Binary, 65535),
+        ] {
+            let mut buf = Vec::new();
+            save_to(Image::new(&px, 2, 3), &mut buf, options)?;
+
+            let img = parse(buf.as_slice())?;
+            assert_eq!(img.get_width(), 3);
+            assert_eq!(img.get_height(), 2);
+            for (l, r) in img.get_pixels().iter().zip(&px) {
+                assert!((*l - *r).length() < 1e-9, "{:?} {:?}", l, r);
+            }
+        }
+
+        let tmp = tempfile::Builder::new().suffix(".ppm").tempfile()?;
+        save(Image::new(&px, 2, 3), tmp.path())?;
+        assert_eq!(load(tmp.path())?.get_pixels(), &px[..]);
+
+        // comments in the header and a maximum value of 15
+        let img = parse(&b"P3 # comment\n1 1\n# another\n15\n15 0 8\n"[..])?;
+        assert_eq!(img.get_pixels(), &[Color::new(255.0, 0.0, 136.0)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let err = |data: &[u8]| parse(data).unwrap_err().to_string();
+
+        assert_eq!(err(b"P5 1 1 255 0"), "unsupported format 'P5'");
+        assert_eq!(err(b"P3 1 x 255"), "invalid header field 'height x'");
+        assert_eq!(err(b"P3 1 1 255 0 0"), "unexpected end of file");
+        assert_eq!(err(b"P3 1 1 255 0 256 0"), "invalid value '256'");
+        assert_eq!(err(b"P6 2 1 255\n\0\0\0"), "unexpected end of file");
+
+        // sizes far beyond the data fail without allocating for them
+        assert_eq!(
+            err(b"P3 200000 200000 255\n1 2 3"),
+            "unexpected end of file"
+        );
+        assert_eq!(
+            err(b"P6 4294967296 4294967296 255\n"),
+            "invalid header field 'size 4294967296x4294967296'"
+        );
+    }
 }
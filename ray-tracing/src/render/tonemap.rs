@@ -0,0 +1,209 @@
+//! Display transforms turning the linear radiance of a render into values
+//! for the integer image formats.
+
+use crate::render::{Color, ImageBuffer};
+
+/// Relative luminance of a linear color with the Rec. 709 primaries.
+pub fn luminance(c: Color) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+/// Encodes a linear value with the sRGB transfer function.
+pub fn srgb_encode(v: f64) -> f64 {
+    if v <= 0.003_130_8 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decodes an sRGB encoded value back to linear.
+pub fn srgb_decode(v: f64) -> f64 {
+    if v <= 0.040_45 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Maps the unbounded radiance into the `0..1` range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    /// Cuts off everything above one.
+    Clamp,
+    /// `L / (1 + L)` on the luminance, keeping the hue.
+    Reinhard,
+    /// Reinhard, mapping the `white` luminance to one.
+    ReinhardExtended { white: f64 },
+    /// The ACES filmic curve fit by Krzysztof Narkowicz.
+    Aces,
+    /// John Hable's filmic curve from Uncharted 2.
+    Filmic,
+}
+
+impl Operator {
+    pub fn apply(&self, c: Color) -> Color {
+        match *self {
+            Self::Clamp => c,
+            Self::Reinhard => scale_luminance(c, |l| l / (1.0 + l)),
+            Self::ReinhardExtended { white } => {
+                scale_luminance(c, |l| l * (1.0 + l / (white * white)) / (1.0 + l))
+            }
+            Self::Aces => map_channels(c, |x| {
+                (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+            }),
+            Self::Filmic => {
+                const EXPOSURE_BIAS: f64 = 2.0;
+                const WHITE: f64 = 11.2;
+
+                let white_scale = 1.0 / hable(WHITE);
+                map_channels(c, |x| hable(x * EXPOSURE_BIAS) * white_scale)
+            }
+        }
+    }
+}
+
+fn hable(x: f64) -> f64 {
+    const A: f64 = 0.15;
+    const B: f64 = 0.50;
+    const C: f64 = 0.10;
+    const D: f64 = 0.20;
+    const E: f64 = 0.02;
+    const F: f64 = 0.30;
+
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+fn map_channels<F: Fn(f64) -> f64>(c: Color, f: F) -> Color {
+    Color::new(f(c.x()), f(c.y()), f(c.z()))
+}
+
+fn scale_luminance<F: Fn(f64) -> f64>(c: Color, f: F) -> Color {
+    let l = luminance(c);
+    if l <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    c * (f(l) / l)
+}
+
+/// Encoding of the tone mapped values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transfer {
+    Linear,
+    /// `v^(1 / gamma)`.
+    Gamma(f64),
+    Srgb,
+}
+
+impl Transfer {
+    pub fn encode(&self, v: f64) -> f64 {
+        match *self {
+            Self::Linear => v,
+            Self::Gamma(g) => v.powf(1.0 / g),
+            Self::Srgb => srgb_encode(v),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneMapper {
+    /// Exposure adjustment in stops, applied before the operator.
+    pub exposure: f64,
+    pub operator: Operator,
+    pub transfer: Transfer,
+}
+
+impl Default for ToneMapper {
+    fn default() -> Self {
+        Self::new(Operator::Clamp, Transfer::Srgb)
+    }
+}
+
+impl ToneMapper {
+    pub fn new(operator: Operator, transfer: Transfer) -> Self {
+        Self {
+            exposure: 0.0,
+            operator,
+            transfer,
+        }
+    }
+
+    pub fn with_exposure(mut self, exposure: f64) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    /// Maps a linear color into the `0..=1` display range.
+    pub fn map(&self, c: Color) -> Color {
+        let c = self.operator.apply(c * 2f64.powf(self.exposure));
+        map_channels(c, |v| self.transfer.encode(v.clamp(0.0, 1.0)))
+    }
+
+    /// Maps the linear image and scales it to the `0..256` range expected
+    /// by the integer image formats.
+    pub fn apply(&self, image: &ImageBuffer) -> ImageBuffer {
+        image.map(|c| self.map(c) * 256.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(l: f64, r: f64) {
+        assert!((l - r).abs() < 1e-6, "{} != {}", l, r);
+    }
+
+    #[test]
+    fn test_srgb() {
+        assert_close(srgb_encode(0.0), 0.0);
+        assert_close(srgb_encode(1.0), 1.0);
+        assert_close(srgb_encode(0.18), 0.461_356);
+        assert_close(srgb_encode(0.001), 0.012_92);
+
+        for i in 0..=20 {
+            let v = i as f64 / 20.0;
+            assert_close(srgb_decode(srgb_encode(v)), v);
+        }
+    }
+
+    #[test]
+    fn test_operators() {
+        let white = Color::new(1.0, 1.0, 1.0);
+
+        assert_eq!(Operator::Reinhard.apply(white), white * 0.5);
+        assert_eq!(
+            Operator::ReinhardExtended { white: 1.0 }.apply(white),
+            white
+        );
+
+        // the hue is kept by the luminance based operators
+        let c = Operator::Reinhard.apply(Color::new(4.0, 2.0, 0.0));
+        assert_close(c.x() / c.y(), 2.0);
+
+        // the curves map zero to zero and approach one
+        for op in [Operator::Aces, Operator::Filmic] {
+            assert_close(op.apply(Color::new(0.0, 0.0, 0.0)).x(), 0.0);
+            let bright = op.apply(white * 100.0).x();
+            assert!(bright > 0.95, "{:?} maps 100 to {}", op, bright);
+        }
+        assert_close(Operator::Filmic.apply(white * 5.6).x(), 1.0);
+    }
+
+    #[test]
+    fn test_tone_mapper() {
+        let tm = ToneMapper::new(Operator::Clamp, Transfer::Linear).with_exposure(1.0);
+        assert_eq!(
+            tm.map(Color::new(0.25, 1.0, -1.0)),
+            Color::new(0.5, 1.0, 0.0)
+        );
+
+        let tm = ToneMapper::new(Operator::Clamp, Transfer::Gamma(2.0));
+        let img = ImageBuffer::from_pixels(vec![Color::new(0.25, 4.0, 0.0)], 1, 1);
+        assert_eq!(
+            tm.apply(&img).get_pixels(),
+            &[Color::new(128.0, 256.0, 0.0)]
+        );
+    }
+}
@@ -0,0 +1,367 @@
+use std::{error, fmt};
+
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    camera::Camera,
+    hittable::Hittable,
+    integrator::Integrator,
+    rand_range,
+    render::{
+        tonemap::{ToneMapper, Transfer},
+        Color, ImageBuffer,
+    },
+    stream_rng, SampleRng,
+};
+
+/// The widest, or tallest, image the settings accept.
+pub const MAX_ASPECT_RATIO: f64 = 32.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingsError {
+    /// The named setting is zero.
+    Zero(&'static str),
+    InvalidGamma(f64),
+    InvalidAspectRatio(f64),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Zero(name) => write!(f, "{} has to be at least 1", name),
+            Self::InvalidGamma(g) => write!(f, "gamma {} has to be positive", g),
+            Self::InvalidAspectRatio(a) => write!(
+                f,
+                "aspect ratio {} is outside of 1/{max}..{max}",
+                a,
+                max = MAX_ASPECT_RATIO
+            ),
+        }
+    }
+}
+
+impl error::Error for SettingsError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderSettings {
+    pub image_width: usize,
+    pub image_height: usize,
+    pub samples_per_pixel: usize,
+    pub max_depth: usize,
+    /// Number of passes, all of them get averaged.
+    pub repetition: usize,
+    /// Display transform of [`Renderer::render`].
+    pub tone: ToneMapper,
+    /// Seed of the sampling, the same seed gives the same image.
+    pub seed: u64,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            image_width: 1920,
+            image_height: 1080,
+            samples_per_pixel: 500,
+            max_depth: 50,
+            repetition: 4,
+            tone: ToneMapper::default(),
+            seed: 0,
+        }
+    }
+}
+
+impl RenderSettings {
+    /// Creates the settings and checks them using [`Self::validate`].
+    pub fn new(
+        image_width: usize,
+        image_height: usize,
+        samples_per_pixel: usize,
+        max_depth: usize,
+        repetition: usize,
+    ) -> Result<Self, SettingsError> {
+        let settings = Self {
+            image_width,
+            image_height,
+            samples_per_pixel,
+            max_depth,
+            repetition,
+            tone: ToneMapper::default(),
+            seed: 0,
+        };
+        settings.validate()?;
+
+        Ok(settings)
+    }
+
+    /// Checks that all the sizes and counts are non-zero, the gamma of the
+    /// display transform is positive and the image isn't wider or taller than
+    /// [`MAX_ASPECT_RATIO`].
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        let counts = [
+            (self.image_width, "image_width"),
+            (self.image_height, "image_height"),
+            (self.samples_per_pixel, "samples_per_pixel"),
+            (self.max_depth, "max_depth"),
+            (self.repetition, "repetition"),
+        ];
+
+        if let Some((_, name)) = counts.iter().find(|(v, _)| *v == 0) {
+            return Err(SettingsError::Zero(name));
+        }
+
+        if let Transfer::Gamma(gamma) = self.tone.transfer {
+            if !(gamma.is_finite() && gamma > 0.0) {
+                return Err(SettingsError::InvalidGamma(gamma));
+            }
+        }
+
+        let aspect = self.aspect_ratio();
+        if !(1.0 / MAX_ASPECT_RATIO..=MAX_ASPECT_RATIO).contains(&aspect) {
+            return Err(SettingsError::InvalidAspectRatio(aspect));
+        }
+
+        Ok(())
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_tone(mut self, tone: ToneMapper) -> Self {
+        self.tone = tone;
+        self
+    }
+
+    pub fn aspect_ratio(&self) -> f64 {
+        self.image_width as f64 / self.image_height as f64
+    }
+}
+
+/// Gets told how far the rendering is, the methods are called from the
+/// worker threads.
+pub trait RenderProgress: Sync {
+    fn pass_started(&self, _pass: usize) {}
+    fn row_finished(&self) {}
+    fn pass_finished(&self, _pass: usize) {}
+}
+
+impl RenderProgress for () {}
+
+pub struct Renderer<I: Integrator> {
+    integrator: I,
+    settings: RenderSettings,
+}
+
+impl<I: Integrator> Renderer<I> {
+    /// # Panics
+    /// If the settings are invalid.
+    pub fn new(integrator: I, settings: RenderSettings) -> Self {
+        if let Err(err) = settings.validate() {
+            panic!("invalid render settings: {}", err);
+        }
+
+        Self {
+            integrator,
+            settings,
+        }
+    }
+
+    /// Get a reference to the renderer's settings.
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    /// Renders the image and returns the average radiance of each pixel.
+    pub fn render_linear<H, P>(&self, world: &H, cam: &Camera, progress: &P) -> ImageBuffer
+    where
+        H: Hittable,
+        P: RenderProgress,
+    {
+        let s = &self.settings;
+        let mut res = vec![Color::new(0.0, 0.0, 0.0); s.image_width * s.image_height];
+
+        for pass in 0..s.repetition {
+            progress.pass_started(pass);
+
+            let data = self.pass(world, cam, progress, pass);
+            for (r, d) in res.iter_mut().zip(data) {
+                *r += d;
+            }
+
+            progress.pass_finished(pass);
+        }
+
+        let scale = 1.0 / (s.samples_per_pixel * s.repetition) as f64;
+        for val in res.iter_mut() {
+            *val *= scale;
+        }
+
+        ImageBuffer::from_pixels(res, s.image_height, s.image_width)
+    }
+
+    /// Renders the image, tone mapped with the settings and scaled to
+    /// `0..256` so it can be written as is.
+    pub fn render<H, P>(&self, world: &H, cam: &Camera, progress: &P) -> ImageBuffer
+    where
+        H: Hittable,
+        P: RenderProgress,
+    {
+        let data = self.render_linear(world, cam, progress);
+        self.settings.tone.apply(&data)
+    }
+
+    /// Sums up the samples of each pixel for one pass, row by row starting
+    /// at the top.
+    ///
+    /// Every pixel of every pass gets its own random stream, so the result
+    /// doesn't depend on how the rows are scheduled.
+    fn pass<H, P>(&self, world: &H, cam: &Camera, progress: &P, pass: usize) -> Vec<Color>
+    where
+        H: Hittable,
+        P: RenderProgress,
+    {
+        let s = &self.settings;
+        let calc = |o, l: usize, rng: &mut SampleRng| {
+            ((o as f64) + rand_range(rng, 0.0..1.0)) / (l - 1).max(1) as f64
+        };
+
+        (0..s.image_height)
+            .into_par_iter()
+            .rev()
+            .map(|j| {
+                let row = (0..s.image_width)
+                    .map(|i| {
+                        let pixel = (pass * s.image_height + j) * s.image_width + i;
+                        let mut rng = stream_rng(s.seed, pixel as u64);
+                        let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+
+                        for _ in 0..s.samples_per_pixel {
+                            let v = calc(j, s.image_height, &mut rng);
+                            let u = calc(i, s.image_width, &mut rng);
+                            let r = cam.get_ray(u, v, &mut rng);
+                            pixel_color +=
+                                self.integrator.ray_color(&r, world, s.max_depth, &mut rng);
+                        }
+
+                        pixel_color
+                    })
+                    .collect::<Vec<_>>();
+
+                progress.row_finished();
+                row
+            })
+            .flatten()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        hittable::HittableList,
+        integrator::{Background, PathTracer},
+        material::Lambartian,
+        ray::{Point, Vec3},
+        render::tonemap::Operator,
+        sphere::Sphere,
+    };
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(RenderSettings::default().validate(), Ok(()));
+
+        let new = |w, h, spp| RenderSettings::new(w, h, spp, 50, 1);
+        assert_eq!(new(0, 10, 1), Err(SettingsError::Zero("image_width")));
+        assert_eq!(
+            new(10, 10, 0),
+            Err(SettingsError::Zero("samples_per_pixel"))
+        );
+        assert_eq!(
+            new(1000, 10, 1),
+            Err(SettingsError::InvalidAspectRatio(100.0))
+        );
+        assert!(new(1, 1, 1).is_ok());
+
+        let gamma = |g| ToneMapper::new(Operator::Clamp, Transfer::Gamma(g));
+        let settings = new(1, 1, 1).unwrap();
+        assert_eq!(
+            settings.with_tone(gamma(0.0)).validate(),
+            Err(SettingsError::InvalidGamma(0.0))
+        );
+        assert!(settings.with_tone(gamma(2.2)).validate().is_ok());
+    }
+
+    #[test]
+    fn test_background_only() {
+        let settings = RenderSettings {
+            image_width: 8,
+            image_height: 4,
+            samples_per_pixel: 2,
+            max_depth: 5,
+            repetition: 2,
+            tone: ToneMapper::new(Operator::Clamp, Transfer::Gamma(2.0)),
+            seed: 0,
+        };
+        let background = Color::new(0.25, 0.0, 1.0);
+        let renderer = Renderer::new(PathTracer::new(Background::Solid(background)), settings);
+
+        let cam = Camera::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            2.0,
+            0.0,
+            1.0,
+        );
+        let world = HittableList::new();
+
+        let linear = renderer.render_linear(&world, &cam, &());
+        assert_eq!(linear.get_width(), 8);
+        assert_eq!(linear.get_height(), 4);
+        for p in linear.get_pixels() {
+            assert!((*p - background).length() < 1e-12);
+        }
+
+        let display = renderer.render(&world, &cam, &());
+        for p in display.get_pixels() {
+            assert!((*p - Color::new(128.0, 0.0, 256.0)).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_seed() {
+        let mat = Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(Point::new(0.0, 0.0, -1.0), 0.5, mat)));
+
+        let cam = Camera::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+            0.1,
+            1.0,
+        );
+        let render = |seed| {
+            let settings = RenderSettings::new(16, 16, 4, 5, 2)
+                .unwrap()
+                .with_seed(seed);
+            Renderer::new(PathTracer::new(Background::Sky), settings).render_linear(
+                &world,
+                &cam,
+                &(),
+            )
+        };
+
+        // the rows are rendered in parallel, in no particular order
+        let first = render(7);
+        assert_eq!(first, render(7));
+        assert_ne!(first, render(8));
+    }
+}
@@ -1,18 +1,18 @@
 use rand::{
     distributions::uniform::{SampleRange, SampleUniform},
-    Rng,
+    Rng, SeedableRng,
 };
 use std::f64::consts::PI;
 
 #[inline]
 pub fn degrees_to_radians(deg: f64) -> f64 {
-    debug_assert!(deg >= 0.0 && deg <= 360.0);
+    debug_assert!((0.0..=360.0).contains(&deg));
     deg * PI / 180.0
 }
 
 #[inline]
 pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
-    if x < min  {
+    if x < min {
         min
     } else if x > max {
         max
@@ -21,11 +21,41 @@ pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
     }
 }
 
+/// The generator used for sampling, it gives the same numbers on every
+/// platform and version.
+pub type SampleRng = rand_pcg::Pcg64Mcg;
+
 #[inline]
-pub fn rand_range<T, R>(range: R) -> T
+pub fn rand_range<T, R, G>(rng: &mut G, range: R) -> T
 where
     T: SampleUniform,
     R: SampleRange<T>,
+    G: Rng + ?Sized,
 {
-    rand::thread_rng().gen_range(range)
+    rng.gen_range(range)
+}
+
+/// SplitMix64 finalizer, spreads similar inputs over the whole range.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// The stream for building a scene out of a seed.
+pub const SCENE_STREAM: u64 = u64::MAX;
+/// The stream for the tables of the noise functions.
+pub const NOISE_STREAM: u64 = u64::MAX - 1;
+/// The stream for picking the split axes of a [`BvhNode`](crate::bvh::BvhNode).
+pub const BVH_STREAM: u64 = u64::MAX - 2;
+
+/// Creates the generator for one of the independent streams of a seed, so
+/// the result doesn't depend on the order of the work.
+///
+/// The streams from zero on belong to the pixels, numbered row by row
+/// through all the passes of a render. The streams at the top of the range
+/// are reserved for building the scene, see [`SCENE_STREAM`] and the
+/// constants next to it, so they never repeat the samples of a pixel.
+pub fn stream_rng(seed: u64, stream: u64) -> SampleRng {
+    SampleRng::seed_from_u64(mix(seed ^ mix(stream.wrapping_add(0x9e37_79b9_7f4a_7c15))))
 }
@@ -1,8 +1,11 @@
+use std::f64::consts::PI;
+
 use crate::{
+    aabb::Aabb,
     cvec::dot,
     hittable::{HitRecord, Hittable},
     material::Mat,
-    ray::{Point, Ray},
+    ray::{Point, Ray, Vec3},
 };
 
 pub struct Sphere {
@@ -19,6 +22,36 @@ impl Sphere {
             mat,
         }
     }
+
+    /// Maps a point on the unit sphere to its texture coordinates.
+    ///
+    /// `u` is the angle around the Y axis from X=-1, `v` the angle from Y=-1
+    /// to Y=+1, both normalized to [0,1].
+    fn get_sphere_uv(p: &Point) -> (f64, f64) {
+        let (theta, phi) = Self::get_sphere_angles(p);
+
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    fn get_sphere_angles(p: &Point) -> (f64, f64) {
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+
+        (theta, phi)
+    }
+
+    /// The derivatives of the surface point along `u` and `v`, for the
+    /// point `p` on the unit sphere.
+    fn get_sphere_tangents(&self, p: &Point) -> (Vec3, Vec3) {
+        let (theta, phi) = Self::get_sphere_angles(p);
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let (sin_phi, cos_phi) = phi.sin_cos();
+
+        let dpdu = Vec3::new(sin_phi * sin_theta, 0.0, cos_phi * sin_theta) * (2.0 * PI);
+        let dpdv = Vec3::new(-cos_phi * cos_theta, sin_theta, sin_phi * cos_theta) * PI;
+
+        (dpdu * self.radius, dpdv * self.radius)
+    }
 }
 
 impl Hittable for Sphere {
@@ -48,8 +81,80 @@ impl Hittable for Sphere {
         rec.p = r.at(rec.t);
         let outward_normal = (rec.p - self.center) / self.radius;
         rec.set_face_normal(r, &outward_normal);
+        let (u, v) = Self::get_sphere_uv(&outward_normal);
+        rec.u = u;
+        rec.v = v;
+        let (tangent, bitangent) = self.get_sphere_tangents(&outward_normal);
+        rec.tangent = tangent;
+        rec.bitangent = bitangent;
         rec.mat = Some(self.mat.clone());
 
         true
     }
+
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        let r = self.radius.abs();
+        let r = Vec3::new(r, r, r);
+        *output_box = Aabb::new(self.center - r, self.center + r);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambartian, render::Color};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_uv() {
+        let mat = Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = Sphere::new(Point::new(0.0, 0.0, 0.0), 2.0, mat);
+        let mut rec = HitRecord::default();
+
+        // hits the +Z side at the equator, a quarter turn from X=-1
+        let r = Ray::new(Point::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(sphere.hit(&r, 0.0, f64::INFINITY, &mut rec));
+        assert!((rec.u - 0.25).abs() < 1e-12);
+        assert!((rec.v - 0.5).abs() < 1e-12);
+
+        // the top pole
+        let r = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(sphere.hit(&r, 0.0, f64::INFINITY, &mut rec));
+        assert!((rec.v - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_tangents() {
+        let mat = Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = Sphere::new(Point::new(1.0, 0.0, 0.0), 2.0, mat);
+        let mut rec = HitRecord::default();
+
+        // a small step along u and v moves the point by the tangents
+        let r = Ray::new(Point::new(2.0, 1.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(sphere.hit(&r, 0.0, f64::INFINITY, &mut rec));
+
+        let d = 1e-6;
+        let (theta, phi) = (rec.v * PI, rec.u * 2.0 * PI);
+        let at = |theta: f64, phi: f64| {
+            sphere.center
+                + sphere.radius
+                    * Vec3::new(
+                        -phi.cos() * theta.sin(),
+                        -theta.cos(),
+                        phi.sin() * theta.sin(),
+                    )
+        };
+
+        let dpdu = (at(theta, phi + 2.0 * PI * d) - at(theta, phi)) / d;
+        let dpdv = (at(theta + PI * d, phi) - at(theta, phi)) / d;
+        assert!((dpdu - rec.tangent).length() < 1e-4, "{:?}", rec.tangent);
+        assert!(
+            (dpdv - rec.bitangent).length() < 1e-4,
+            "{:?}",
+            rec.bitangent
+        );
+        assert!(dot(rec.tangent, rec.normal).abs() < 1e-9);
+    }
 }
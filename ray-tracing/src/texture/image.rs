@@ -0,0 +1,339 @@
+use std::path::Path;
+
+use super::{lerp, Texture};
+use crate::{
+    ray::Point,
+    render::{pfm, png, ppm, tonemap, Color, ImageBuffer, ImageError},
+};
+
+/// How texture coordinates outside of `0..1` are mapped onto the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    /// Tiles the image.
+    Repeat,
+    /// Extends the border pixels.
+    Clamp,
+    /// Tiles the image, flipping every other tile.
+    Mirror,
+}
+
+impl Wrap {
+    /// Maps a pixel index onto `0..len`.
+    fn index(self, i: i64, len: usize) -> usize {
+        let len = len as i64;
+
+        let i = match self {
+            Self::Repeat => i.rem_euclid(len),
+            Self::Clamp => i.clamp(0, len - 1),
+            Self::Mirror => {
+                let i = i.rem_euclid(2 * len);
+                if i < len {
+                    i
+                } else {
+                    2 * len - 1 - i
+                }
+            }
+        };
+
+        i as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// The pixel the coordinates fall into.
+    Nearest,
+    /// Blends the four closest pixel centers.
+    Bilinear,
+}
+
+/// The encoding of the pixel values of an integer image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Linear,
+    Srgb,
+}
+
+/// An image together with the versions of it at half the size of the last,
+/// down to a single pixel.
+pub struct MipMap {
+    levels: Vec<ImageBuffer>,
+}
+
+impl MipMap {
+    /// Builds the levels by averaging boxes of 2x2 pixels, the last row and
+    /// column of an odd sized level are averaged on their own.
+    pub fn new(image: ImageBuffer) -> Self {
+        let mut levels = vec![image];
+
+        loop {
+            let last = levels.last().expect("the full size level is always there");
+            let (width, height) = (last.get_width(), last.get_height());
+            if width <= 1 && height <= 1 {
+                break;
+            }
+
+            let (w, h) = (width.div_ceil(2), height.div_ceil(2));
+            let mut next = ImageBuffer::new(h, w);
+            for y in 0..h {
+                for x in 0..w {
+                    let mut sum = Color::new(0.0, 0.0, 0.0);
+                    let mut count = 0.0;
+                    for sy in 2 * y..(2 * y + 2).min(height) {
+                        for sx in 2 * x..(2 * x + 2).min(width) {
+                            sum += last.get_pixel(sx, sy);
+                            count += 1.0;
+                        }
+                    }
+                    next.set_pixel(x, y, sum / count);
+                }
+            }
+
+            levels.push(next);
+        }
+
+        Self { levels }
+    }
+
+    pub fn levels(&self) -> &[ImageBuffer] {
+        &self.levels
+    }
+}
+
+/// An image wrapped over the surface coordinates, with `v` going up from
+/// the bottom row.
+///
+/// The pixels are used as linear colors with one being white, see
+/// [`from_ldr`](Self::from_ldr) for the images of the integer formats.
+pub struct ImageTexture {
+    mipmap: MipMap,
+    pub wrap: Wrap,
+    pub filter: Filter,
+    /// The mip level to sample, each one halves the resolution of the last.
+    /// Fractions blend between two levels, larger values give a box
+    /// prefiltered, blurrier texture.
+    ///
+    /// The level is the same for every lookup, it isn't chosen by the size
+    /// of the pixel on the surface as the rays don't carry their footprint.
+    pub lod: f64,
+}
+
+impl ImageTexture {
+    /// Creates a texture sampling the nearest pixel and clamping the
+    /// coordinates.
+    pub fn new(image: ImageBuffer) -> Self {
+        Self {
+            mipmap: MipMap::new(image),
+            wrap: Wrap::Clamp,
+            filter: Filter::Nearest,
+            lod: 0.0,
+        }
+    }
+
+    /// Creates the texture from an image of [`ppm::load`] or [`png::load`],
+    /// where the largest sample of every bit depth is 255.
+    pub fn from_ldr(image: &ImageBuffer, space: ColorSpace) -> Self {
+        let decode = |v: f64| {
+            let v = (v / 255.0).clamp(0.0, 1.0);
+            match space {
+                ColorSpace::Linear => v,
+                ColorSpace::Srgb => tonemap::srgb_decode(v),
+            }
+        };
+
+        Self::new(image.map(|c| Color::new(decode(c.x()), decode(c.y()), decode(c.z()))))
+    }
+
+    /// Loads a PNG or PPM image as sRGB, or a PFM image as linear, depending
+    /// on the extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ImageError> {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        Ok(match ext.as_str() {
+            "png" => Self::from_ldr(&png::load(path)?, ColorSpace::Srgb),
+            "ppm" => Self::from_ldr(&ppm::load(path)?, ColorSpace::Srgb),
+            "pfm" => Self::new(pfm::load(path)?),
+            _ => return Err(ImageError::UnsupportedFormat(ext)),
+        })
+    }
+
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_lod(mut self, lod: f64) -> Self {
+        self.lod = lod;
+        self
+    }
+
+    /// The image at full resolution.
+    pub fn image(&self) -> &ImageBuffer {
+        &self.mipmap.levels[0]
+    }
+
+    pub fn mipmap(&self) -> &MipMap {
+        &self.mipmap
+    }
+
+    fn texel(&self, level: &ImageBuffer, x: i64, y: i64) -> Color {
+        level.get_pixel(
+            self.wrap.index(x, level.get_width()),
+            self.wrap.index(y, level.get_height()),
+        )
+    }
+
+    fn sample(&self, level: &ImageBuffer, u: f64, v: f64) -> Color {
+        // pixel coordinates with the origin in the top left corner
+        let x = u * level.get_width() as f64;
+        let y = (1.0 - v) * level.get_height() as f64;
+
+        match self.filter {
+            Filter::Nearest => self.texel(level, x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // relative to the centers of the pixels
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let top = lerp(self.texel(level, x0, y0), self.texel(level, x0 + 1, y0), tx);
+                let bottom = lerp(
+                    self.texel(level, x0, y0 + 1),
+                    self.texel(level, x0 + 1, y0 + 1),
+                    tx,
+                );
+                lerp(top, bottom, ty)
+            }
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point) -> Color {
+        let levels = self.mipmap.levels();
+
+        // Cyan as a debugging aid for a missing image.
+        if levels[0].get_width() == 0 || levels[0].get_height() == 0 {
+            return Color::new(0.0, 1.0, 1.0);
+        }
+
+        let lod = self.lod.clamp(0.0, (levels.len() - 1) as f64);
+        let level = lod.floor() as usize;
+        let t = lod - level as f64;
+
+        let c = self.sample(&levels[level], u, v);
+        if t > 0.0 {
+            lerp(c, self.sample(&levels[level + 1], u, v), t)
+        } else {
+            c
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_texture() {
+        let red = Color::new(1.0, 0.0, 0.0);
+        let blue = Color::new(0.0, 0.0, 1.0);
+        let mut image = ImageBuffer::new(2, 2);
+        image.set_pixel(0, 0, red);
+        image.set_pixel(1, 1, blue);
+
+        let tex = ImageTexture::new(image);
+        let p = Point::new(0.0, 0.0, 0.0);
+
+        // v = 1 is the top row
+        assert_eq!(tex.value(0.25, 0.75, &p), red);
+        assert_eq!(tex.value(0.75, 0.25, &p), blue);
+        assert_eq!(tex.value(1.0, 0.0, &p), blue);
+        assert_eq!(tex.value(-1.0, 2.0, &p), red);
+
+        let empty = ImageTexture::new(ImageBuffer::new(0, 0));
+        assert_eq!(empty.value(0.5, 0.5, &p), Color::new(0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_wrap() {
+        let indices = |wrap: Wrap| (-4..8).map(|i| wrap.index(i, 3)).collect::<Vec<_>>();
+
+        assert_eq!(indices(Wrap::Repeat), [2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1]);
+        assert_eq!(indices(Wrap::Clamp), [0, 0, 0, 0, 0, 1, 2, 2, 2, 2, 2, 2]);
+        assert_eq!(indices(Wrap::Mirror), [2, 2, 1, 0, 0, 1, 2, 2, 1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_bilinear() {
+        let image = ImageBuffer::from_pixels(
+            vec![Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0)],
+            1,
+            2,
+        );
+        let p = Point::new(0.0, 0.0, 0.0);
+        let tex = ImageTexture::new(image).with_filter(Filter::Bilinear);
+
+        // the pixel centers and half way between them
+        assert_eq!(tex.value(0.25, 0.5, &p).x(), 0.0);
+        assert_eq!(tex.value(0.75, 0.5, &p).x(), 1.0);
+        assert_eq!(tex.value(0.5, 0.5, &p).x(), 0.5);
+
+        // blends with the other side only when repeating
+        assert_eq!(tex.value(0.0, 0.5, &p).x(), 0.0);
+        let tex = tex.with_wrap(Wrap::Repeat);
+        assert_eq!(tex.value(0.0, 0.5, &p).x(), 0.5);
+    }
+
+    #[test]
+    fn test_mipmap() {
+        let pixels = (0..15).map(|i| Color::new(i as f64, 0.0, 0.0)).collect();
+        let tex = ImageTexture::new(ImageBuffer::from_pixels(pixels, 3, 5));
+
+        let sizes: Vec<_> = tex
+            .mipmap()
+            .levels()
+            .iter()
+            .map(|l| (l.get_width(), l.get_height()))
+            .collect();
+        assert_eq!(sizes, [(5, 3), (3, 2), (2, 1), (1, 1)]);
+
+        let first = &tex.mipmap().levels()[1];
+        assert_eq!(first.get_pixel(0, 0).x(), (0.0 + 1.0 + 5.0 + 6.0) / 4.0);
+        assert_eq!(first.get_pixel(2, 0).x(), (4.0 + 9.0) / 2.0);
+        assert_eq!(first.get_pixel(2, 1).x(), 14.0);
+
+        let p = Point::new(0.0, 0.0, 0.0);
+        let top = tex.mipmap().levels()[3].get_pixel(0, 0);
+        assert_eq!(tex.with_lod(10.0).value(0.1, 0.9, &p), top);
+    }
+
+    #[test]
+    fn test_load() -> Result<(), ImageError> {
+        let pixels = [Color::new(255.0, 0.0, 188.0)];
+        let tmp = tempfile::Builder::new().suffix(".png").tempfile()?;
+        png::save(ImageBuffer::from_pixels(pixels.to_vec(), 1, 1), tmp.path())?;
+
+        let tex = ImageTexture::load(tmp.path())?;
+        let c = tex.image().get_pixel(0, 0);
+        assert_eq!((c.x(), c.y()), (1.0, 0.0));
+        assert!((c.z() - 0.5).abs() < 0.01, "{}", c.z());
+
+        assert!(matches!(
+            ImageTexture::load("texture.bmp"),
+            Err(ImageError::UnsupportedFormat(_))
+        ));
+
+        Ok(())
+    }
+}
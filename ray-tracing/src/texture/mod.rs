@@ -0,0 +1,292 @@
+mod image;
+pub use image::*;
+
+use std::sync::Arc;
+
+use crate::{
+    noise::{Perlin, Worley},
+    ray::Point,
+    render::Color,
+};
+
+pub type Tex = Arc<dyn Texture>;
+
+/// A color varying over a surface.
+pub trait Texture: Send + Sync {
+    /// The color at the surface coordinates `u`, `v` of the point `p`.
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color;
+}
+
+pub struct SolidColor {
+    pub color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl From<Color> for SolidColor {
+    fn from(color: Color) -> Self {
+        Self::new(color)
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point) -> Color {
+        self.color
+    }
+}
+
+/// A 3D checker pattern alternating between two textures.
+pub struct Checker {
+    pub odd: Tex,
+    pub even: Tex,
+    /// The pattern flips every π / frequency, so there are frequency / π
+    /// cells per unit length.
+    pub frequency: f64,
+}
+
+impl Checker {
+    pub fn new(odd: Tex, even: Tex) -> Self {
+        Self {
+            odd,
+            even,
+            frequency: 10.0,
+        }
+    }
+
+    pub fn with_colors(odd: Color, even: Color) -> Self {
+        Self::new(
+            Arc::new(SolidColor::new(odd)),
+            Arc::new(SolidColor::new(even)),
+        )
+    }
+
+    pub fn with_frequency(mut self, frequency: f64) -> Self {
+        self.frequency = frequency;
+        self
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color {
+        let f = self.frequency;
+        let sines = (f * p.x()).sin() * (f * p.y()).sin() * (f * p.z()).sin();
+
+        if sines < 0.0 {
+            self.odd.value(u, v, p)
+        } else {
+            self.even.value(u, v, p)
+        }
+    }
+}
+
+fn lerp(a: Color, b: Color, t: f64) -> Color {
+    (1.0 - t) * a + t * b
+}
+
+/// Gray Perlin noise.
+pub struct NoiseTexture {
+    pub noise: Perlin,
+    pub scale: f64,
+}
+
+impl NoiseTexture {
+    pub fn new(noise: Perlin, scale: f64) -> Self {
+        Self { noise, scale }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point) -> Color {
+        let n = self.noise.noise(&(self.scale * *p));
+        Color::new(1.0, 1.0, 1.0) * 0.5 * (1.0 + n)
+    }
+}
+
+/// Gray turbulence, the sum of several octaves of Perlin noise.
+pub struct Turbulence {
+    pub noise: Perlin,
+    pub scale: f64,
+    pub depth: usize,
+}
+
+impl Turbulence {
+    pub fn new(noise: Perlin, scale: f64) -> Self {
+        Self {
+            noise,
+            scale,
+            depth: 7,
+        }
+    }
+
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+}
+
+impl Texture for Turbulence {
+    fn value(&self, _u: f64, _v: f64, p: &Point) -> Color {
+        let t = self.noise.turb(&(self.scale * *p), self.depth);
+        Color::new(1.0, 1.0, 1.0) * t.min(1.0)
+    }
+}
+
+/// Sine bands along Z, their phase distorted by turbulence into veins.
+pub struct Marble {
+    pub noise: Perlin,
+    pub scale: f64,
+    pub depth: usize,
+    pub light: Color,
+    pub dark: Color,
+}
+
+impl Marble {
+    pub fn new(noise: Perlin, scale: f64) -> Self {
+        Self {
+            noise,
+            scale,
+            depth: 7,
+            light: Color::new(1.0, 1.0, 1.0),
+            dark: Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn with_palette(mut self, light: Color, dark: Color) -> Self {
+        self.light = light;
+        self.dark = dark;
+        self
+    }
+}
+
+impl Texture for Marble {
+    fn value(&self, _u: f64, _v: f64, p: &Point) -> Color {
+        let phase = self.scale * p.z() + 10.0 * self.noise.turb(p, self.depth);
+        lerp(self.dark, self.light, 0.5 * (1.0 + phase.sin()))
+    }
+}
+
+/// Growth rings around the Y axis, warped by turbulence.
+pub struct Wood {
+    pub noise: Perlin,
+    /// Rings per unit length.
+    pub scale: f64,
+    /// How far the rings are pushed around by the turbulence.
+    pub distortion: f64,
+    pub depth: usize,
+    pub light: Color,
+    pub dark: Color,
+}
+
+impl Wood {
+    pub fn new(noise: Perlin, scale: f64) -> Self {
+        Self {
+            noise,
+            scale,
+            distortion: 1.0,
+            depth: 4,
+            light: Color::new(0.76, 0.56, 0.33),
+            dark: Color::new(0.45, 0.27, 0.12),
+        }
+    }
+
+    pub fn with_distortion(mut self, distortion: f64) -> Self {
+        self.distortion = distortion;
+        self
+    }
+
+    pub fn with_palette(mut self, light: Color, dark: Color) -> Self {
+        self.light = light;
+        self.dark = dark;
+        self
+    }
+}
+
+impl Texture for Wood {
+    fn value(&self, _u: f64, _v: f64, p: &Point) -> Color {
+        let r = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        let rings = self.scale * r + self.distortion * self.noise.turb(p, self.depth);
+        // sharpen the late wood at the end of every ring
+        let t = (rings - rings.floor()).powf(3.0);
+        lerp(self.light, self.dark, t)
+    }
+}
+
+/// Worley noise, dark at the feature points and getting lighter towards
+/// the cell borders.
+pub struct Cellular {
+    pub noise: Worley,
+    pub scale: f64,
+    pub light: Color,
+    pub dark: Color,
+}
+
+impl Cellular {
+    pub fn new(noise: Worley, scale: f64) -> Self {
+        Self {
+            noise,
+            scale,
+            light: Color::new(1.0, 1.0, 1.0),
+            dark: Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn with_palette(mut self, light: Color, dark: Color) -> Self {
+        self.light = light;
+        self.dark = dark;
+        self
+    }
+}
+
+impl Texture for Cellular {
+    fn value(&self, _u: f64, _v: f64, p: &Point) -> Color {
+        let (f1, _) = self.noise.distances(&(self.scale * *p));
+        lerp(self.dark, self.light, f1.min(1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checker() {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let white = Color::new(1.0, 1.0, 1.0);
+        let checker = Checker::with_colors(black, white).with_frequency(1.0);
+
+        assert_eq!(checker.value(0.0, 0.0, &Point::new(1.0, 1.0, 1.0)), white);
+        assert_eq!(checker.value(0.0, 0.0, &Point::new(-1.0, 1.0, 1.0)), black);
+    }
+
+    #[test]
+    fn test_procedural() {
+        let p = Point::new(0.3, 1.7, -2.4);
+        let in_range = |c: Color| c.data().iter().all(|v| (0.0..=1.0).contains(v));
+
+        let textures: Vec<Tex> = vec![
+            Arc::new(NoiseTexture::new(Perlin::new(0), 4.0)),
+            Arc::new(Turbulence::new(Perlin::new(0), 4.0)),
+            Arc::new(Marble::new(Perlin::new(0), 4.0)),
+            Arc::new(Wood::new(Perlin::new(0), 4.0)),
+            Arc::new(Cellular::new(Worley::new(0), 4.0)),
+        ];
+        for tex in &textures {
+            assert!(in_range(tex.value(0.0, 0.0, &p)));
+        }
+
+        // the noise is zero on the lattice
+        let noise = NoiseTexture::new(Perlin::new(0), 1.0);
+        assert_eq!(
+            noise.value(0.0, 0.0, &Point::new(1.0, 2.0, 3.0)),
+            Color::new(0.5, 0.5, 0.5)
+        );
+
+        let a = Marble::new(Perlin::new(1), 4.0).value(0.0, 0.0, &p);
+        let b = Marble::new(Perlin::new(1), 4.0).value(0.0, 0.0, &p);
+        assert_eq!(a, b);
+    }
+}
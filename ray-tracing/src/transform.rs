@@ -0,0 +1,548 @@
+use std::ops;
+
+use crate::{
+    cvec::{cross, dot},
+    ray::{Point, Vec3},
+};
+
+/// A row major 4x4 matrix, vectors are treated as columns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    m: [[f64; 4]; 4],
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl From<[[f64; 4]; 4]> for Mat4 {
+    fn from(m: [[f64; 4]; 4]) -> Self {
+        Self { m }
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Self = Self {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub fn new(m: [[f64; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    pub fn data(&self) -> &[[f64; 4]; 4] {
+        &self.m
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        Self::new([
+            [factors.x(), 0.0, 0.0, 0.0],
+            [0.0, factors.y(), 0.0, 0.0],
+            [0.0, 0.0, factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Counter clockwise rotation around the axis through the origin.
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        let u = axis.unit_vector();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let (x, y, z) = (u.x(), u.y(), u.z());
+        let c = 1.0 - cos;
+
+        Self::new([
+            [
+                cos + x * x * c,
+                x * y * c - z * sin,
+                x * z * c + y * sin,
+                0.0,
+            ],
+            [
+                y * x * c + z * sin,
+                cos + y * y * c,
+                y * z * c - x * sin,
+                0.0,
+            ],
+            [
+                z * x * c - y * sin,
+                z * y * c + x * sin,
+                cos + z * z * c,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// The view transform of a camera at `eye` looking at `target`.
+    ///
+    /// Maps `eye` to the origin and the view direction onto -Z, with `up`
+    /// ending up in the YZ plane.
+    pub fn look_at(eye: Point, target: Point, up: Vec3) -> Self {
+        let w = (eye - target).unit_vector();
+        let u = cross(&up, &w).unit_vector();
+        let v = cross(&w, &u);
+
+        Self::new([
+            [u.x(), u.y(), u.z(), -dot(u, eye)],
+            [v.x(), v.y(), v.z(), -dot(v, eye)],
+            [w.x(), w.y(), w.z(), -dot(w, eye)],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut res = [[0.0; 4]; 4];
+        for (i, row) in res.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = self.m[j][i];
+            }
+        }
+        Self::new(res)
+    }
+
+    /// Inverts the matrix using Gauss-Jordan elimination with partial
+    /// pivoting, returns `None` if it is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Self::IDENTITY.m;
+
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&l, &r| a[l][col].abs().total_cmp(&a[r][col].abs()))
+                .unwrap_or(col);
+
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let p = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= p;
+                inv[col][j] *= p;
+            }
+
+            for row in 0..4 {
+                if row == col {
+                    continue;
+                }
+                let f = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= f * a[col][j];
+                    inv[row][j] -= f * inv[col][j];
+                }
+            }
+        }
+
+        Some(Self::new(inv))
+    }
+
+    /// Transforms a point, dividing by the homogeneous coordinate.
+    pub fn transform_point(&self, p: Point) -> Point {
+        let m = &self.m;
+        let row = |i: usize| m[i][0] * p.x() + m[i][1] * p.y() + m[i][2] * p.z() + m[i][3];
+
+        let w = row(3);
+        let res = Point::new(row(0), row(1), row(2));
+        if w == 1.0 {
+            res
+        } else {
+            res / w
+        }
+    }
+
+    /// Transforms a direction, the translation is ignored.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        let row = |i: usize| m[i][0] * v.x() + m[i][1] * v.y() + m[i][2] * v.z();
+
+        Vec3::new(row(0), row(1), row(2))
+    }
+
+    /// Transforms a normal by the transpose of the matrix, so this has to
+    /// be called on the inverse of the transform applied to the surface.
+    pub fn transform_normal(&self, n: Vec3) -> Vec3 {
+        self.transpose().transform_vector(n)
+    }
+}
+
+impl ops::Mul for Mat4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut res = [[0.0; 4]; 4];
+        for (i, row) in res.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Self::new(res)
+    }
+}
+
+impl ops::Index<(usize, usize)> for Mat4 {
+    type Output = f64;
+
+    fn index(&self, (row, col): (usize, usize)) -> &Self::Output {
+        &self.m[row][col]
+    }
+}
+
+/// A rotation quaternion `w + xi + yj + zk`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Quat {
+    pub const IDENTITY: Self = Self {
+        w: 1.0,
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Self { w, x, y, z }
+    }
+
+    /// Counter clockwise rotation around the axis through the origin.
+    pub fn from_axis_angle(axis: Vec3, degrees: f64) -> Self {
+        let u = axis.unit_vector();
+        let (sin, cos) = (degrees.to_radians() / 2.0).sin_cos();
+
+        Self::new(cos, u.x() * sin, u.y() * sin, u.z() * sin)
+    }
+
+    fn dot(&self, rhs: &Self) -> f64 {
+        self.w * rhs.w + self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    pub fn length(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Self {
+        let l = self.length();
+        Self::new(self.w / l, self.x / l, self.y / l, self.z / l)
+    }
+
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// Rotates the vector, the quaternion has to be normalized.
+    pub fn rotate(&self, v: Vec3) -> Vec3 {
+        let q = Vec3::new(self.x, self.y, self.z);
+        let t = 2.0 * cross(&q, &v);
+        v + self.w * t + cross(&q, &t)
+    }
+
+    /// Spherical linear interpolation along the shorter arc.
+    pub fn slerp(&self, other: &Self, t: f64) -> Self {
+        let mut cos = self.dot(other);
+        let mut other = *other;
+
+        if cos < 0.0 {
+            cos = -cos;
+            other = Self::new(-other.w, -other.x, -other.y, -other.z);
+        }
+
+        let (a, b) = if cos > 0.9995 {
+            // nearly the same rotation, fall back to a linear blend
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+
+        Self::new(
+            a * self.w + b * other.w,
+            a * self.x + b * other.x,
+            a * self.y + b * other.y,
+            a * self.z + b * other.z,
+        )
+        .normalize()
+    }
+
+    pub fn to_mat4(&self) -> Mat4 {
+        let Self { w, x, y, z } = self.normalize();
+
+        Mat4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+impl ops::Mul for Quat {
+    type Output = Self;
+
+    /// Hamilton product, the result first rotates by `rhs` then by `self`.
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}
+
+/// A matrix together with its inverse.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    m: Mat4,
+    m_inv: Mat4,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl From<Quat> for Transform {
+    fn from(q: Quat) -> Self {
+        let m = q.to_mat4();
+        // rotations are orthogonal
+        Self {
+            m,
+            m_inv: m.transpose(),
+        }
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        m: Mat4::IDENTITY,
+        m_inv: Mat4::IDENTITY,
+    };
+
+    /// Returns `None` if the matrix is singular.
+    pub fn new(m: Mat4) -> Option<Self> {
+        m.inverse().map(|m_inv| Self { m, m_inv })
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Self {
+            m: Mat4::translation(offset),
+            m_inv: Mat4::translation(-offset),
+        }
+    }
+
+    /// # Panics
+    /// If one of the factors is zero.
+    pub fn scaling(factors: Vec3) -> Self {
+        assert!(
+            factors.x() != 0.0 && factors.y() != 0.0 && factors.z() != 0.0,
+            "scaling by zero is not invertible"
+        );
+        let inv = Vec3::new(1.0 / factors.x(), 1.0 / factors.y(), 1.0 / factors.z());
+
+        Self {
+            m: Mat4::scaling(factors),
+            m_inv: Mat4::scaling(inv),
+        }
+    }
+
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        let m = Mat4::rotation(axis, degrees);
+        Self {
+            m,
+            m_inv: m.transpose(),
+        }
+    }
+
+    pub fn look_at(eye: Point, target: Point, up: Vec3) -> Self {
+        let m = Mat4::look_at(eye, target, up);
+        let m_inv = m.inverse().expect("the rotation part is orthonormal");
+        Self { m, m_inv }
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.m
+    }
+
+    pub fn inverse_matrix(&self) -> &Mat4 {
+        &self.m_inv
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            m: self.m_inv,
+            m_inv: self.m,
+        }
+    }
+
+    /// Applies `self` after `first`.
+    pub fn then(&self, first: &Self) -> Self {
+        Self {
+            m: self.m * first.m,
+            m_inv: first.m_inv * self.m_inv,
+        }
+    }
+
+    pub fn point(&self, p: Point) -> Point {
+        self.m.transform_point(p)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.m.transform_vector(v)
+    }
+
+    /// Transforms a surface normal, the result is not normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        self.m_inv.transform_normal(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(l: Vec3, r: Vec3) {
+        assert!((l - r).length() < 1e-9, "{:?} != {:?}", l, r);
+    }
+
+    fn assert_mat_close(l: &Mat4, r: &Mat4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((l[(i, j)] - r[(i, j)]).abs() < 1e-9, "{:?} != {:?}", l, r);
+            }
+        }
+    }
+
+    fn setup() -> Mat4 {
+        Mat4::translation(Vec3::new(1.0, -2.0, 3.0))
+            * Mat4::rotation(Vec3::new(1.0, 1.0, 0.0), 30.0)
+            * Mat4::scaling(Vec3::new(2.0, 0.5, 3.0))
+    }
+
+    #[test]
+    fn test_inverse() {
+        let m = setup();
+        let inv = m.inverse().unwrap();
+
+        assert_mat_close(&(m * inv), &Mat4::IDENTITY);
+        assert_mat_close(&(inv * m), &Mat4::IDENTITY);
+
+        let singular = Mat4::scaling(Vec3::new(1.0, 0.0, 1.0));
+        assert!(singular.inverse().is_none());
+    }
+
+    #[test]
+    fn test_transpose() {
+        let m = setup();
+        assert_eq!(m.transpose().transpose(), m);
+        assert_eq!(m.transpose()[(0, 3)], m[(3, 0)]);
+        assert_eq!(m.transpose()[(2, 1)], m[(1, 2)]);
+    }
+
+    #[test]
+    fn test_transform() {
+        let t = Transform::translation(Vec3::new(1.0, 2.0, 3.0))
+            .then(&Transform::scaling(Vec3::new(2.0, 1.0, 1.0)));
+
+        assert_close(
+            t.point(Point::new(1.0, 1.0, 1.0)),
+            Point::new(3.0, 3.0, 4.0),
+        );
+        assert_close(t.vector(Vec3::new(1.0, 1.0, 1.0)), Vec3::new(2.0, 1.0, 1.0));
+        assert_close(
+            t.inverse().point(Point::new(3.0, 3.0, 4.0)),
+            Point::new(1.0, 1.0, 1.0),
+        );
+
+        // the normal of the plane x + y = 0 after stretching along x
+        let n = t.normal(Vec3::new(1.0, 1.0, 0.0));
+        let tangent = t.vector(Vec3::new(1.0, -1.0, 0.0));
+        assert!(dot(n, tangent).abs() < 1e-12);
+        assert_close(n, Vec3::new(0.5, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_look_at() {
+        let eye = Point::new(13.0, 2.0, 3.0);
+        let target = Point::new(0.0, 0.0, 0.0);
+        let t = Transform::look_at(eye, target, Vec3::new(0.0, 1.0, 0.0));
+
+        assert_close(t.point(eye), Point::new(0.0, 0.0, 0.0));
+
+        let dist = (eye - target).length();
+        assert_close(t.point(target), Point::new(0.0, 0.0, -dist));
+
+        let up = t.vector(Vec3::new(0.0, 1.0, 0.0));
+        assert!(up.x().abs() < 1e-12 && up.y() > 0.0);
+    }
+
+    #[test]
+    fn test_quat() {
+        let axis = Vec3::new(1.0, 2.0, 3.0);
+        let q = Quat::from_axis_angle(axis, 75.0);
+        let m = Mat4::rotation(axis, 75.0);
+        let v = Vec3::new(-1.0, 0.5, 2.0);
+
+        assert_close(q.rotate(v), m.transform_vector(v));
+        assert_mat_close(&q.to_mat4(), &m);
+        assert_close(q.conjugate().rotate(q.rotate(v)), v);
+
+        let z = Vec3::new(0.0, 0.0, 1.0);
+        let q90 = Quat::from_axis_angle(z, 90.0);
+        assert_close(
+            (q90 * q90).rotate(Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(-1.0, 0.0, 0.0),
+        );
+
+        let half = Quat::IDENTITY.slerp(&q90, 0.5);
+        assert_close(
+            half.rotate(Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(0.5f64.sqrt(), 0.5f64.sqrt(), 0.0),
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    cvec::{cross, dot},
+    hittable::{HitRecord, Hittable, HittableList},
+    material::Mat,
+    ray::{Point, Ray, Vec3},
+};
+
+/// Texture coordinates of a vertex.
+pub type Uv = (f64, f64);
+
+/// Smallest sine of the angle between the ray and the triangle, or between
+/// the texture coordinate edges, that still counts as not parallel.
+const EPSILON: f64 = 1e-8;
+const BOX_PADDING: f64 = 1e-4;
+
+/// Intersects the ray with the triangle using the Möller–Trumbore algorithm.
+///
+/// Returns the distance along the ray and the barycentric coordinates of
+/// the hit point relative to `p1` and `p2`.
+fn intersect(p: [Point; 3], r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+    let edge1 = p[1] - p[0];
+    let edge2 = p[2] - p[0];
+
+    let pvec = cross(&r.direction(), &edge2);
+    let det = dot(edge1, pvec);
+
+    // the ray is parallel to the triangle, relative to the size of the
+    // triangle and the ray so that tiny triangles still get hit
+    let scale = edge1.length() * edge2.length() * r.direction().length();
+    if det.abs() <= EPSILON * scale {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let tvec = r.origin() - p[0];
+    let b1 = dot(tvec, pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = cross(&tvec, &edge1);
+    let b2 = dot(r.direction(), qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = dot(edge2, qvec) * inv_det;
+    if t < t_min || t_max < t {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+fn interpolate<T>(v: [T; 3], b1: f64, b2: f64) -> T
+where
+    T: std::ops::Mul<f64, Output = T> + std::ops::Add<Output = T>,
+{
+    let [v0, v1, v2] = v;
+    v0 * (1.0 - b1 - b2) + v1 * b1 + v2 * b2
+}
+
+fn fill_record(
+    p: [Point; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[Uv; 3]>,
+    mat: &Mat,
+    r: &Ray,
+    (t, b1, b2): (f64, f64, f64),
+    rec: &mut HitRecord,
+) {
+    rec.t = t;
+    rec.p = r.at(t);
+
+    let geometric = cross(&(p[1] - p[0]), &(p[2] - p[0])).unit_vector();
+    rec.set_face_normal(r, &geometric);
+
+    if let Some(n) = normals {
+        // keep the shading normal on the side the ray is coming from
+        let shading = interpolate(n, b1, b2).unit_vector();
+        rec.normal = if dot(shading, rec.normal) < 0.0 {
+            -shading
+        } else {
+            shading
+        };
+    }
+
+    let (u, v) = match uvs {
+        Some([uv0, uv1, uv2]) => (
+            interpolate([uv0.0, uv1.0, uv2.0], b1, b2),
+            interpolate([uv0.1, uv1.1, uv2.1], b1, b2),
+        ),
+        None => (b1, b2),
+    };
+    rec.u = u;
+    rec.v = v;
+
+    let (tangent, bitangent) = tangents(p, uvs);
+    rec.tangent = tangent;
+    rec.bitangent = bitangent;
+    rec.mat = Some(mat.clone());
+}
+
+/// The derivatives of the point along `u` and `v`, zero if the texture
+/// coordinates don't span the triangle.
+fn tangents(p: [Point; 3], uvs: Option<[Uv; 3]>) -> (Vec3, Vec3) {
+    let edge1 = p[1] - p[0];
+    let edge2 = p[2] - p[0];
+
+    // without texture coordinates u and v are the barycentric coordinates
+    let [uv0, uv1, uv2] = match uvs {
+        Some(uvs) => uvs,
+        None => return (edge1, edge2),
+    };
+    let (du1, dv1) = (uv1.0 - uv0.0, uv1.1 - uv0.1);
+    let (du2, dv2) = (uv2.0 - uv0.0, uv2.1 - uv0.1);
+
+    let det = du1 * dv2 - du2 * dv1;
+    let scale = du1.hypot(dv1) * du2.hypot(dv2);
+    if det.abs() <= EPSILON * scale {
+        return (Vec3::default(), Vec3::default());
+    }
+
+    let inv_det = 1.0 / det;
+    (
+        (edge1 * dv2 - edge2 * dv1) * inv_det,
+        (edge2 * du1 - edge1 * du2) * inv_det,
+    )
+}
+
+fn bounding_box(p: [Point; 3]) -> Aabb {
+    let min = p[0].min(&p[1]).min(&p[2]);
+    let max = p[0].max(&p[1]).max(&p[2]);
+
+    Aabb::new(min, max).padded(BOX_PADDING)
+}
+
+/// A single triangle owning its vertices.
+pub struct Triangle {
+    pub vertices: [Point; 3],
+    pub normals: Option<[Vec3; 3]>,
+    pub uvs: Option<[Uv; 3]>,
+    pub mat: Mat,
+}
+
+impl Triangle {
+    pub fn new(v0: Point, v1: Point, v2: Point, mat: Mat) -> Self {
+        Self {
+            vertices: [v0, v1, v2],
+            normals: None,
+            uvs: None,
+            mat,
+        }
+    }
+
+    pub fn with_attributes(
+        vertices: [Point; 3],
+        normals: Option<[Vec3; 3]>,
+        uvs: Option<[Uv; 3]>,
+        mat: Mat,
+    ) -> Self {
+        Self {
+            vertices,
+            normals,
+            uvs,
+            mat,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        match intersect(self.vertices, r, t_min, t_max) {
+            Some(hit) => {
+                fill_record(
+                    self.vertices,
+                    self.normals,
+                    self.uvs,
+                    &self.mat,
+                    r,
+                    hit,
+                    rec,
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        *output_box = bounding_box(self.vertices);
+        true
+    }
+}
+
+/// An indexed triangle mesh.
+///
+/// All the triangles share the vertex buffers of the mesh, normals and
+/// texture coordinates are optional but if given need one entry per vertex.
+pub struct TriangleMesh {
+    positions: Vec<Point>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<Uv>>,
+    indices: Vec<[usize; 3]>,
+    mat: Mat,
+}
+
+impl TriangleMesh {
+    /// # Panics
+    /// If the attribute buffers don't match the positions in length or an
+    /// index points past the end of the buffers.
+    pub fn new(
+        positions: Vec<Point>,
+        normals: Option<Vec<Vec3>>,
+        uvs: Option<Vec<Uv>>,
+        indices: Vec<[usize; 3]>,
+        mat: Mat,
+    ) -> Self {
+        if let Some(ref n) = normals {
+            assert_eq!(n.len(), positions.len(), "incorrect normal length");
+        }
+        if let Some(ref uv) = uvs {
+            assert_eq!(uv.len(), positions.len(), "incorrect uv length");
+        }
+        assert!(
+            indices.iter().flatten().all(|&i| i < positions.len()),
+            "vertex index out of range"
+        );
+
+        Self {
+            positions,
+            normals,
+            uvs,
+            indices,
+            mat,
+        }
+    }
+
+    /// Get a reference to the triangle mesh's positions.
+    pub fn positions(&self) -> &[Point] {
+        &self.positions
+    }
+
+    /// Get a reference to the triangle mesh's normals.
+    pub fn normals(&self) -> Option<&[Vec3]> {
+        self.normals.as_deref()
+    }
+
+    /// Get a reference to the triangle mesh's texture coordinates.
+    pub fn uvs(&self) -> Option<&[Uv]> {
+        self.uvs.as_deref()
+    }
+
+    /// Get a reference to the triangle mesh's indices.
+    pub fn indices(&self) -> &[[usize; 3]] {
+        &self.indices
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Splits the mesh into its triangles, each one referencing the shared
+    /// buffers, so that they can be sorted into a bvh individually.
+    pub fn into_list(self) -> HittableList {
+        let mesh = Arc::new(self);
+        let mut list = HittableList::with_capacity(mesh.len());
+
+        for index in 0..mesh.len() {
+            list.add(Arc::new(MeshTriangle {
+                mesh: mesh.clone(),
+                index,
+            }));
+        }
+
+        list
+    }
+
+    fn gather<T: Copy>(buf: &[T], idx: [usize; 3]) -> [T; 3] {
+        [buf[idx[0]], buf[idx[1]], buf[idx[2]]]
+    }
+}
+
+/// A triangle of a [`TriangleMesh`].
+pub struct MeshTriangle {
+    mesh: Arc<TriangleMesh>,
+    index: usize,
+}
+
+impl MeshTriangle {
+    fn vertices(&self) -> [Point; 3] {
+        TriangleMesh::gather(&self.mesh.positions, self.mesh.indices[self.index])
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let vertices = self.vertices();
+
+        match intersect(vertices, r, t_min, t_max) {
+            Some(hit) => {
+                let idx = self.mesh.indices[self.index];
+                let normals = self
+                    .mesh
+                    .normals
+                    .as_ref()
+                    .map(|n| TriangleMesh::gather(n, idx));
+                let uvs = self
+                    .mesh
+                    .uvs
+                    .as_ref()
+                    .map(|uv| TriangleMesh::gather(uv, idx));
+
+                fill_record(vertices, normals, uvs, &self.mesh.mat, r, hit, rec);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        *output_box = bounding_box(self.vertices());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambartian, render::Color};
+
+    fn mat() -> Mat {
+        Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    fn setup() -> Triangle {
+        Triangle::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(0.0, 1.0, 0.0),
+            mat(),
+        )
+    }
+
+    #[test]
+    fn test_hit() {
+        let tri = setup();
+        let r = Ray::new(Point::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+
+        assert!(tri.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert_eq!(rec.t, 1.0);
+        assert_eq!(rec.p, Point::new(0.25, 0.25, 0.0));
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(rec.front_face);
+        assert_eq!((rec.u, rec.v), (0.25, 0.25));
+    }
+
+    #[test]
+    fn test_miss() {
+        let tri = setup();
+        let mut rec = HitRecord::default();
+
+        let outside = Ray::new(Point::new(0.75, 0.75, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(!tri.hit(&outside, 0.001, f64::INFINITY, &mut rec));
+
+        let parallel = Ray::new(Point::new(-1.0, 0.25, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(!tri.hit(&parallel, 0.001, f64::INFINITY, &mut rec));
+
+        let behind = Ray::new(Point::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(!tri.hit(&behind, 0.001, f64::INFINITY, &mut rec));
+    }
+
+    #[test]
+    fn test_tiny() {
+        let s = 1e-5;
+        let tri = Triangle::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(s, 0.0, 0.0),
+            Point::new(0.0, s, 0.0),
+            mat(),
+        );
+        let mut rec = HitRecord::default();
+
+        let r = Ray::new(
+            Point::new(0.25 * s, 0.25 * s, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        );
+        assert!(tri.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert_eq!(rec.t, 1.0);
+
+        let parallel = Ray::new(Point::new(-s, 0.25 * s, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(!tri.hit(&parallel, 0.001, f64::INFINITY, &mut rec));
+    }
+
+    #[test]
+    fn test_mesh() {
+        let positions = vec![
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(1.0, 1.0, 0.0),
+            Point::new(0.0, 1.0, 0.0),
+        ];
+        let normals = vec![Vec3::new(0.0, 0.0, 1.0); 4];
+        let uvs = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let indices = vec![[0, 1, 2], [0, 2, 3]];
+
+        let mesh = TriangleMesh::new(positions, Some(normals), Some(uvs), indices, mat());
+        let list = mesh.into_list();
+        assert_eq!(list.len(), 2);
+
+        let r = Ray::new(Point::new(0.25, 0.75, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut rec = HitRecord::default();
+
+        assert!(list.hit(&r, 0.001, f64::INFINITY, &mut rec));
+        assert!(!rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+        assert!((rec.u - 0.25).abs() < 1e-12);
+        assert!((rec.v - 0.75).abs() < 1e-12);
+        assert_eq!(rec.tangent, Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(rec.bitangent, Vec3::new(0.0, 1.0, 0.0));
+
+        let mut bbox = Aabb::default();
+        assert!(list.bounding_box(&mut bbox));
+        assert!(bbox.max().z() > bbox.min().z());
+    }
+}
@@ -0,0 +1,254 @@
+//! Renders small scenes at fixed seeds and compares them to the images in
+//! `tests/reference`.
+//!
+//! Run with `UPDATE_REFERENCE=1` to write new reference images instead, e.g.
+//! after an intended change of the output. On a mismatch the render and a
+//! difference image are written to the cargo target directory.
+
+use std::{env, fs, path::PathBuf, sync::Arc};
+
+use ray_tracing::{
+    aarect::XzRect,
+    camera::Camera,
+    cuboid::Cuboid,
+    hittable::{HittableList, SahBvh},
+    instance::Instance,
+    integrator::{Background, PathTracer},
+    material::{Dielectric, DiffuseLight, Lambartian, Metal},
+    plane::Plane,
+    ray::{Point, Vec3},
+    render::{
+        compare::{compare, diff_image, Tolerance},
+        pfm, png,
+        tonemap::ToneMapper,
+        Color, ImageBuffer,
+    },
+    renderer::{RenderSettings, Renderer},
+    sphere::Sphere,
+    triangle::TriangleMesh,
+};
+
+/// Room for platform differences in the floating point math, which can send
+/// single paths elsewhere.
+///
+/// A path carries at most the brightest light, so one of them moves a pixel
+/// by no more than 8 / 32 in the emissive scene and less in the others.
+/// The PSNR isn't checked, at the peak of one it is the same as the RMSE.
+const TOLERANCE: Tolerance = Tolerance {
+    max_rmse: 0.01,
+    min_psnr: f64::NEG_INFINITY,
+    max_error: 0.3,
+};
+
+struct Scene {
+    world: HittableList,
+    cam: Camera,
+    background: Background,
+    settings: RenderSettings,
+}
+
+fn camera(lookfrom: Point, lookat: Point, vfov: f64) -> Camera {
+    Camera::new(
+        lookfrom,
+        lookat,
+        Vec3::new(0.0, 1.0, 0.0),
+        vfov,
+        1.0,
+        0.0,
+        1.0,
+    )
+}
+
+fn settings(samples_per_pixel: usize, seed: u64) -> RenderSettings {
+    RenderSettings::new(32, 32, samples_per_pixel, 8, 1)
+        .unwrap()
+        .with_seed(seed)
+}
+
+fn materials() -> Scene {
+    let mut world = HittableList::new();
+    let ground = Arc::new(Lambartian::new(Color::new(0.8, 0.8, 0.0)));
+    let center = Arc::new(Lambartian::new(Color::new(0.1, 0.2, 0.5)));
+    let left = Arc::new(Dielectric::new(1.5));
+    let right = Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.1));
+
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, -100.5, -1.0),
+        100.0,
+        ground,
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, 0.0, -1.0),
+        0.5,
+        center,
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(-1.0, 0.0, -1.0),
+        0.5,
+        left,
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(1.0, 0.0, -1.0),
+        0.5,
+        right,
+    )));
+
+    Scene {
+        world,
+        cam: camera(Point::new(0.0, 0.5, 2.0), Point::new(0.0, 0.0, -1.0), 50.0),
+        background: Background::Sky,
+        settings: settings(16, 1),
+    }
+}
+
+fn emissive() -> Scene {
+    let mut world = HittableList::new();
+    let white = Arc::new(Lambartian::new(Color::new(0.73, 0.73, 0.73)));
+    let light = Arc::new(DiffuseLight::new(Color::new(8.0, 8.0, 8.0)));
+
+    world.add(Arc::new(Plane::new(
+        Point::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        white.clone(),
+    )));
+    world.add(Arc::new(Cuboid::new(
+        Point::new(-0.5, 0.0, -0.5),
+        Point::new(0.5, 1.0, 0.5),
+        white,
+    )));
+    world.add(Arc::new(XzRect::new(-1.0, 1.0, -1.0, 1.0, 2.5, light)));
+
+    Scene {
+        world,
+        cam: camera(Point::new(2.0, 2.0, 3.0), Point::new(0.0, 0.5, 0.0), 50.0),
+        background: Background::Solid(Color::new(0.0, 0.0, 0.0)),
+        settings: settings(32, 2),
+    }
+}
+
+fn mesh() -> Scene {
+    let red = Arc::new(Lambartian::new(Color::new(0.7, 0.2, 0.2)));
+    let grey = Arc::new(Lambartian::new(Color::new(0.5, 0.5, 0.5)));
+
+    let tetrahedron = TriangleMesh::new(
+        vec![
+            Point::new(1.0, 1.0, 1.0),
+            Point::new(1.0, -1.0, -1.0),
+            Point::new(-1.0, 1.0, -1.0),
+            Point::new(-1.0, -1.0, 1.0),
+        ],
+        None,
+        None,
+        vec![[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]],
+        red,
+    );
+    let bvh = Arc::new(SahBvh::new(&tetrahedron.into_list()));
+    let instance = Instance::new(bvh)
+        .scale(Vec3::new(0.5, 0.5, 0.5))
+        .rotate(Vec3::new(0.0, 1.0, 0.0), 30.0)
+        .translate(Vec3::new(0.0, 0.5, 0.0));
+
+    let mut world = HittableList::new();
+    world.add(Arc::new(instance));
+    world.add(Arc::new(Plane::new(
+        Point::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        grey,
+    )));
+
+    Scene {
+        world,
+        cam: camera(Point::new(0.0, 1.5, 3.0), Point::new(0.0, 0.5, 0.0), 40.0),
+        background: Background::Sky,
+        settings: settings(16, 3),
+    }
+}
+
+fn render(scene: &Scene) -> ImageBuffer {
+    Renderer::new(PathTracer::new(scene.background), scene.settings).render_linear(
+        &scene.world,
+        &scene.cam,
+        &(),
+    )
+}
+
+fn check(name: &str, scene: Scene) {
+    let image = render(&scene);
+
+    let reference_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/reference")
+        .join(format!("{}.pfm", name));
+
+    if env::var_os("UPDATE_REFERENCE").is_some() {
+        pfm::save(&image, &reference_path).unwrap();
+        return;
+    }
+
+    let reference = pfm::load(&reference_path).unwrap_or_else(|err| {
+        panic!(
+            "unable to load {}: {}, run with UPDATE_REFERENCE=1 to create it",
+            reference_path.display(),
+            err
+        )
+    });
+
+    // the reference is stored as 32 bit floats
+    let image = image.map(|c| {
+        Color::new(
+            c.x() as f32 as f64,
+            c.y() as f32 as f64,
+            c.z() as f32 as f64,
+        )
+    });
+
+    let diff = compare(&image, &reference).unwrap_or_else(|| {
+        panic!(
+            "{}: size {}x{} doesn't match the reference {}x{}",
+            name,
+            image.get_width(),
+            image.get_height(),
+            reference.get_width(),
+            reference.get_height()
+        )
+    });
+
+    if diff.within(&TOLERANCE) {
+        return;
+    }
+
+    let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("reference");
+    fs::create_dir_all(&out).unwrap();
+
+    let actual_path = out.join(format!("{}.pfm", name));
+    let diff_path = out.join(format!("{}-diff.png", name));
+    pfm::save(&image, &actual_path).unwrap();
+    png::save(
+        ToneMapper::default().apply(&diff_image(&image, &reference)),
+        &diff_path,
+    )
+    .unwrap();
+
+    panic!(
+        "{} differs from its reference: {:?}, allowed {:?}\nrender: {}\ndifference: {}",
+        name,
+        diff,
+        TOLERANCE,
+        actual_path.display(),
+        diff_path.display()
+    );
+}
+
+#[test]
+fn reference_materials() {
+    check("materials", materials());
+}
+
+#[test]
+fn reference_emissive() {
+    check("emissive", emissive());
+}
+
+#[test]
+fn reference_mesh() {
+    check("mesh", mesh());
+}
@@ -0,0 +1,283 @@
+use std::{fmt, path::PathBuf};
+
+use ray_tracing::{
+    render::{
+        png::BitDepth,
+        tonemap::{Operator, Transfer},
+    },
+    renderer::{RenderSettings, SettingsError},
+};
+
+pub const USAGE: &str = "\
+Usage: ray-tracing-weekend [OPTIONS]
+
+Options:
+  -w, --width <PIXELS>     image width [default: 1920]
+  -H, --height <PIXELS>    image height [default: width / aspect]
+  -a, --aspect <RATIO>     aspect ratio as `16:9` or `1.78`, not with `-H`
+                           [default: 16:9]
+  -s, --samples <N>        samples per pixel [default: 500]
+  -d, --depth <N>          maximum number of bounces [default: 50]
+  -r, --repetition <N>     number of averaged passes [default: 4]
+      --seed <N>           seed of the scene and the sampling [default: 0]
+  -g, --gamma <GAMMA>      display gamma, or `srgb` [default: srgb]
+  -t, --tonemap <OP>       tone mapping: clamp, reinhard, aces, filmic [default: clamp]
+  -e, --exposure <STOPS>   exposure adjustment [default: 0]
+  -o, --output <PATH>      output file, `-` writes a ppm to stdout [default: main]
+  -f, --format <FORMAT>    output format: ppm, png, pfm, hdr, exr
+                           [default: from the extension]
+  -b, --bit-depth <BITS>   png and ppm bit depth: 8, 16 [default: 8]
+  -h, --help               print this help";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Ppm,
+    Png,
+    Pfm,
+    Hdr,
+    Exr,
+}
+
+impl OutputFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "ppm" => Some(Self::Ppm),
+            "png" => Some(Self::Png),
+            "pfm" => Some(Self::Pfm),
+            "hdr" => Some(Self::Hdr),
+            "exr" => Some(Self::Exr),
+            _ => None,
+        }
+    }
+
+    /// If the format stores the linear values instead of display values.
+    pub fn is_hdr(self) -> bool {
+        matches!(self, Self::Pfm | Self::Hdr | Self::Exr)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliError {
+    Help,
+    MissingValue(String),
+    InvalidValue(String, String),
+    UnknownArgument(String),
+    /// Two arguments that can't be used together.
+    Conflict(&'static str, &'static str),
+    StdoutFormat(OutputFormat),
+    Settings(SettingsError),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Help => write!(f, "{}", USAGE),
+            Self::MissingValue(arg) => write!(f, "missing value for '{}'", arg),
+            Self::InvalidValue(arg, v) => write!(f, "invalid value '{}' for '{}'", v, arg),
+            Self::UnknownArgument(arg) => write!(f, "unknown argument '{}'", arg),
+            Self::Conflict(l, r) => write!(f, "'{}' can't be used together with '{}'", l, r),
+            Self::StdoutFormat(format) => {
+                write!(f, "only ppm can be written to stdout, not {:?}", format)
+            }
+            Self::Settings(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args {
+    pub settings: RenderSettings,
+    pub output: PathBuf,
+    pub format: OutputFormat,
+    pub bit_depth: BitDepth,
+}
+
+fn parse_operator(s: &str) -> Option<Operator> {
+    match s.to_ascii_lowercase().as_str() {
+        "clamp" => Some(Operator::Clamp),
+        "reinhard" => Some(Operator::Reinhard),
+        "aces" => Some(Operator::Aces),
+        "filmic" => Some(Operator::Filmic),
+        _ => None,
+    }
+}
+
+fn parse_aspect(s: &str) -> Option<f64> {
+    let aspect = match s.split_once(':') {
+        Some((w, h)) => w.parse::<f64>().ok()? / h.parse::<f64>().ok()?,
+        None => s.parse().ok()?,
+    };
+
+    Some(aspect).filter(|a| a.is_finite() && *a > 0.0)
+}
+
+/// Parses the arguments, without the program name.
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, CliError> {
+    let mut settings = RenderSettings::default();
+    let mut height = None;
+    let mut aspect = None;
+    let mut output = PathBuf::from("main");
+    let mut format = None;
+    let mut bit_depth = BitDepth::Eight;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Err(CliError::Help);
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| CliError::MissingValue(arg.clone()))?;
+        let invalid = || CliError::InvalidValue(arg.clone(), value.clone());
+        let number = || value.parse::<usize>().map_err(|_| invalid());
+
+        match arg.as_str() {
+            "-w" | "--width" => settings.image_width = number()?,
+            "-H" | "--height" => height = Some(number()?),
+            "-a" | "--aspect" => aspect = Some(parse_aspect(&value).ok_or_else(invalid)?),
+            "-s" | "--samples" => settings.samples_per_pixel = number()?,
+            "-d" | "--depth" => settings.max_depth = number()?,
+            "-r" | "--repetition" => settings.repetition = number()?,
+            "--seed" => settings.seed = value.parse().map_err(|_| invalid())?,
+            "-g" | "--gamma" if value.eq_ignore_ascii_case("srgb") => {
+                settings.tone.transfer = Transfer::Srgb
+            }
+            "-g" | "--gamma" => {
+                settings.tone.transfer = Transfer::Gamma(value.parse().map_err(|_| invalid())?)
+            }
+            "-t" | "--tonemap" => {
+                settings.tone.operator = parse_operator(&value).ok_or_else(invalid)?
+            }
+            "-e" | "--exposure" => {
+                settings.tone.exposure = value
+                    .parse()
+                    .ok()
+                    .filter(|e: &f64| e.is_finite())
+                    .ok_or_else(invalid)?
+            }
+            "-o" | "--output" => output = PathBuf::from(&value),
+            "-f" | "--format" => format = Some(OutputFormat::parse(&value).ok_or_else(invalid)?),
+            "-b" | "--bit-depth" => {
+                bit_depth = match value.as_str() {
+                    "8" => BitDepth::Eight,
+                    "16" => BitDepth::Sixteen,
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => return Err(CliError::UnknownArgument(arg)),
+        }
+    }
+
+    settings.image_height = match (height, aspect) {
+        (Some(_), Some(_)) => return Err(CliError::Conflict("--height", "--aspect")),
+        (Some(height), None) => height,
+        (None, aspect) => (settings.image_width as f64 / aspect.unwrap_or(16.0 / 9.0)) as usize,
+    };
+    settings.validate().map_err(CliError::Settings)?;
+
+    let format = format
+        .or_else(|| {
+            output
+                .extension()
+                .and_then(|e| OutputFormat::parse(&e.to_string_lossy()))
+        })
+        .unwrap_or(OutputFormat::Ppm);
+
+    if output.as_os_str() == "-" && format != OutputFormat::Ppm {
+        return Err(CliError::StdoutFormat(format));
+    }
+
+    Ok(Args {
+        settings,
+        output,
+        format,
+        bit_depth,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ray_tracing::render::tonemap::ToneMapper;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let res = parse(args("")).unwrap();
+
+        assert_eq!(res.settings, RenderSettings::default());
+        assert_eq!(res.output, PathBuf::from("main"));
+        assert_eq!(res.format, OutputFormat::Ppm);
+        assert_eq!(res.bit_depth, BitDepth::Eight);
+        assert_eq!(res.settings.tone, ToneMapper::default());
+    }
+
+    #[test]
+    fn test_parse() {
+        let res = parse(args(
+            "-w 400 --aspect 2:1 -s 10 --depth 5 -g 2.2 -o out.ppm",
+        ))
+        .unwrap();
+
+        assert_eq!(res.settings.image_width, 400);
+        assert_eq!(res.settings.image_height, 200);
+        assert_eq!(res.settings.samples_per_pixel, 10);
+        assert_eq!(res.settings.max_depth, 5);
+        assert_eq!(res.settings.tone.transfer, Transfer::Gamma(2.2));
+        assert_eq!(res.output, PathBuf::from("out.ppm"));
+
+        let res = parse(args("-w 400 -H 300 --seed 42")).unwrap();
+        assert_eq!(res.settings.image_height, 300);
+        assert_eq!(res.settings.seed, 42);
+
+        let res = parse(args("-o render.PNG -b 16")).unwrap();
+        assert_eq!(res.format, OutputFormat::Png);
+        assert_eq!(res.bit_depth, BitDepth::Sixteen);
+
+        let res = parse(args("-o render.png -f ppm")).unwrap();
+        assert_eq!(res.format, OutputFormat::Ppm);
+
+        let res = parse(args("-t aces -e -1.5 -g sRGB")).unwrap();
+        assert_eq!(
+            res.settings.tone,
+            ToneMapper::new(Operator::Aces, Transfer::Srgb).with_exposure(-1.5)
+        );
+
+        let res = parse(args("-o render.exr")).unwrap();
+        assert_eq!(res.format, OutputFormat::Exr);
+        assert!(res.format.is_hdr());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse(args("--help")), Err(CliError::Help));
+        assert_eq!(
+            parse(args("-w")),
+            Err(CliError::MissingValue("-w".to_string()))
+        );
+        assert_eq!(
+            parse(args("-s many")),
+            Err(CliError::InvalidValue("-s".to_string(), "many".to_string()))
+        );
+        assert_eq!(
+            parse(args("--frobnicate 1")),
+            Err(CliError::UnknownArgument("--frobnicate".to_string()))
+        );
+        assert_eq!(
+            parse(args("-H 300 -a 2")),
+            Err(CliError::Conflict("--height", "--aspect"))
+        );
+        assert_eq!(
+            parse(args("-o - -f png")),
+            Err(CliError::StdoutFormat(OutputFormat::Png))
+        );
+        assert_eq!(
+            parse(args("-d 0")),
+            Err(CliError::Settings(SettingsError::Zero("max_depth")))
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    io,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -7,14 +8,21 @@ use std::{
     time::Duration,
 };
 
+use cli::{CliError, OutputFormat};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use ray_tracing::render::{ppm, Color, Image};
-use setup::{IMAGE_HEIGHT, IMAGE_WIDTH, REPETITION};
+use ray_tracing::{
+    render::{
+        exr, hdr, pfm,
+        png::{self, BitDepth},
+        ppm, ImageBuffer,
+    },
+    renderer::RenderSettings,
+};
 
+mod cli;
 mod setup;
 
-fn create_image() -> thread::Result<Vec<Color>> {
-
+fn create_image(settings: RenderSettings) -> thread::Result<ImageBuffer> {
     // ProgressBar
     let mp = MultiProgress::new();
 
@@ -31,8 +39,8 @@ fn create_image() -> thread::Result<Vec<Color>> {
         pb
     };
 
-    let pb_run = setup(REPETITION);
-    let pb_curr = setup(IMAGE_HEIGHT);
+    let pb_run = setup(settings.repetition);
+    let pb_curr = setup(settings.image_height);
 
     let pb_run1 = pb_run.clone();
     let pb_curr1 = pb_curr.clone();
@@ -51,7 +59,7 @@ fn create_image() -> thread::Result<Vec<Color>> {
     });
 
     let data = thread::spawn(move || {
-        let res = setup::run(pb_run.clone(), pb_curr.clone());
+        let res = setup::run(settings, pb_run.clone(), pb_curr.clone());
         for pb in [pb_curr, pb_run] {
             pb.finish();
         }
@@ -68,16 +76,51 @@ fn create_image() -> thread::Result<Vec<Color>> {
 }
 
 fn main() {
-    // Image
-    let path = "main";
-
-    println!("Running");
+    let args = match cli::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(CliError::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    let settings = args.settings;
 
-    let data = create_image().expect("unable to get the data, due to some error");
+    eprintln!("Running");
 
-    println!("Writing data");
-    let img = Image::new(&data, IMAGE_HEIGHT, IMAGE_WIDTH);
+    let data = create_image(settings).expect("unable to get the data, due to some error");
 
-    ppm::save(img, path).expect("Something went terribly wrong here");
-    println!("Done");
+    eprintln!("Writing data");
+    // the integer formats need tone mapped values
+    let data = if args.format.is_hdr() {
+        data
+    } else {
+        args.settings.tone.apply(&data)
+    };
+    let img = &data;
+
+    match args.format {
+        OutputFormat::Ppm => {
+            let max_value = match args.bit_depth {
+                BitDepth::Eight => 255,
+                BitDepth::Sixteen => 65535,
+            };
+            let options = ppm::Options::new(ppm::Encoding::Binary, max_value);
+
+            if args.output.as_os_str() == "-" {
+                ppm::save_to(img, io::stdout().lock(), options)
+            } else {
+                ppm::save_with(img, &args.output, options)
+            }
+        }
+        OutputFormat::Png => png::save_with_depth(img, &args.output, args.bit_depth),
+        OutputFormat::Pfm => pfm::save(img, &args.output),
+        OutputFormat::Hdr => hdr::save(img, &args.output),
+        OutputFormat::Exr => exr::save(img, &args.output),
+    }
+    .expect("Something went terribly wrong here");
+    eprintln!("Done");
 }